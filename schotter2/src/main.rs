@@ -9,6 +9,8 @@ const LINE_WIDTH: f32 = 0.06;
 const MARGIN: u32 = 35;
 const WIDTH: u32 = COLS * SIZE + 2 * MARGIN;
 const HEIGHT: u32 = ROWS * SIZE + 2 * MARGIN;
+// Loosening an offset limit past this removes it
+const MAX_OFFSET_LIMIT: f32 = 2.0;
 
 fn main() {
     nannou::app(model).update(update).loop_mode(LoopMode::wait()).run()
@@ -41,6 +43,8 @@ struct Model {
     random_seed: u64,
     disp_adj: f32,
     rot_adj: f32,
+    max_offset_x: f32,
+    max_offset_y: f32,
     gravel: Vec<Stone>
 }
 
@@ -56,6 +60,8 @@ fn model(app: &App) -> Model {
     let random_seed = random_range(0, 1000000);
     let disp_adj = 1.0;
     let rot_adj = 1.0;
+    let max_offset_x = f32::INFINITY;
+    let max_offset_y = f32::INFINITY;
 
     let mut gravel = Vec::new();
     for y in 0..ROWS {
@@ -69,6 +75,8 @@ fn model(app: &App) -> Model {
         random_seed,
         disp_adj,
         rot_adj,
+        max_offset_x,
+        max_offset_y,
         gravel,
     }
 }
@@ -82,6 +90,8 @@ fn update(_app: &App, model: &mut Model, _update: Update) {
         stone.x_offset = disp_factor * rng.gen_range(-0.5..0.5);
         stone.y_offset = disp_factor * rng.gen_range(-0.5..0.5);
         stone.rotation = rot_factor * rng.gen_range(-PI / 4.0..PI / 4.0);
+        stone.x_offset = stone.x_offset.clamp(-model.max_offset_x, model.max_offset_x);
+        stone.y_offset = stone.y_offset.clamp(-model.max_offset_y, model.max_offset_y);
     }
 }
 
@@ -119,22 +129,48 @@ fn key_pressed(app: &App, model: &mut Model, key: Key) {
                 .capture_frame(app.exe_name().unwrap() + ".png");
         }
         Key::Up => {
-            model.disp_adj += 0.1;
+            model.disp_adj += 0.1 * step_scale(app);
         }
         Key::Down => {
-            if model.disp_adj > 0.0 {
-                model.disp_adj -= 0.1;
-            }
+            model.disp_adj = (model.disp_adj - 0.1 * step_scale(app)).max(0.0);
         }
         Key::Right => {
-            model.rot_adj += 0.1;
+            model.rot_adj += 0.1 * step_scale(app);
         }
         Key::Left => {
-            if model.rot_adj > 0.0 {
-                model.rot_adj -= 0.1;
-            }
+            model.rot_adj = (model.rot_adj - 0.1 * step_scale(app)).max(0.0);
+        }
+        // X and Y tighten the horizontal and vertical offset limits; with Alt they loosen
+        Key::X => {
+            model.max_offset_x = step_limit(app, model.max_offset_x);
+        }
+        Key::Y => {
+            model.max_offset_y = step_limit(app, model.max_offset_y);
         }
         _other_key => {}
     }
 
 }
+
+// Move an offset limit one step, tighter or with Alt held looser. No limit is
+// infinity, which tightening starts down from MAX_OFFSET_LIMIT.
+fn step_limit(app: &App, limit: f32) -> f32 {
+    let step = 0.1 * step_scale(app);
+    if app.keys.mods.alt() {
+        let limit = limit + step;
+        if limit >= MAX_OFFSET_LIMIT { f32::INFINITY } else { limit }
+    } else {
+        (limit.min(MAX_OFFSET_LIMIT) - step).max(0.0)
+    }
+}
+
+// Keyboard adjustments are coarser with Shift held and finer with Ctrl held.
+fn step_scale(app: &App) -> f32 {
+    if app.keys.mods.shift() {
+        5.0
+    } else if app.keys.mods.ctrl() {
+        0.1
+    } else {
+        1.0
+    }
+}
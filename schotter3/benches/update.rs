@@ -0,0 +1,25 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+// Not every field is read by the benchmark
+#[allow(dead_code)]
+#[path = "../src/stones.rs"]
+mod stones;
+use stones::{place_stones, Stone};
+
+const ROWS: u32 = 200;
+const COLS: u32 = 200;
+
+fn place(c: &mut Criterion) {
+    let mut gravel = Vec::new();
+    for y in 0..ROWS {
+        for x in 0..COLS {
+            gravel.push(Stone::new(x as f32, y as f32));
+        }
+    }
+    c.bench_function("place_stones 200x200", |b| {
+        b.iter(|| place_stones(&mut gravel, ROWS, black_box(42), black_box(43), 1.0, 1.0))
+    });
+}
+
+criterion_group!(benches, place);
+criterion_main!(benches);
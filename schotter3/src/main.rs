@@ -1,15 +1,21 @@
 use nannou::prelude::*;
-use nannou::rand::rngs::StdRng;
-use nannou::rand::{Rng, SeedableRng};
+use nannou::rand::{thread_rng, Rng};
 use nannou_conrod::prelude::*;
+use std::collections::HashSet;
+use std::fs;
+
+mod names;
+mod stones;
+use stones::{place_stones, Stone};
 
 const ROWS: u32 = 22;
 const COLS: u32 = 12;
 const SIZE: u32 = 30;
 const LINE_WIDTH: f32 = 0.06;
 const MARGIN: u32 = 35;
-const WIDTH: u32 = COLS * SIZE + 2 * MARGIN;
-const HEIGHT: u32 = ROWS * SIZE + 2 * MARGIN;
+const SWEEP_MAX: f32 = 2.0;
+// The top of the offset limit sliders, which stands for no limit
+const MAX_OFFSET_LIMIT: f32 = 2.0;
 
 fn main() {
     nannou::app(model).update(update).loop_mode(LoopMode::wait()).run()
@@ -23,56 +29,148 @@ widget_ids! {
         rot_label,
         rot_slider,
         randomize,
-        seed_label,
-        seed_text,
+        disp_seed_button,
+        disp_seed_text,
+        rot_seed_button,
+        rot_seed_text,
+        seed_names,
+        hex_toggle,
+        locks_toggle,
+        smooth_toggle,
+        fade_label,
+        fade_slider,
+        snap_label,
+        snap_slider,
+        burst_label,
+        burst_slider,
+        pin_corners_toggle,
+        pin_edges_toggle,
+        breathe_amount_label,
+        breathe_amount_slider,
+        breathe_speed_label,
+        breathe_speed_slider,
+        auto_reseed_label,
+        auto_reseed_slider,
+        max_x_label,
+        max_x_slider,
+        max_y_label,
+        max_y_slider,
+        seed_max_label,
+        seed_max_text,
     }
 }
 
-struct Stone {
-    x: f32,
-    y: f32,
-    x_offset: f32,
-    y_offset: f32,
-    rotation: f32,
-}
-
-impl Stone {
-    fn new(x: f32, y: f32) -> Self {
-        let x_offset = 0.0;
-        let y_offset = 0.0;
-        let rotation = 0.0;
-        Stone {
-            x,
-            y,
-            x_offset,
-            y_offset,
-            rotation,
-        }
-    }
+// Progress through rendering every combination of displacement and rotation
+struct Sweep {
+    disp_steps: u32,
+    rot_steps: u32,
+    next: u32,
+    dir: String,
 }
 
 struct Model {
     ui: Ui,
     ids: Ids,
+    ui_scale: f64,
     main_window: WindowId,
-    random_seed: u64,
+    ui_window: WindowId,
+    ui_visible: bool,
+    disp_seed: u64,
+    rot_seed: u64,
+    seed_hex: bool,
+    disp_seed_edit: Option<(String, u64)>,
+    rot_seed_edit: Option<(String, u64)>,
+    seed_max: u64,
+    seed_max_edit: Option<String>,
     disp_adj: f32,
     rot_adj: f32,
+    settings: Settings,
+    transitioning: bool,
+    fade_from: Option<(u64, u64)>,
+    fade_frame: u32,
+    burst_count: u32,
+    burst_remaining: u32,
+    last_reseed: f32,
+    sweep: Option<Sweep>,
+    removed: HashSet<(u32, u32)>,
+    removed_order: Vec<(u32, u32)>,
+    seed_list: Vec<(u64, u64)>,
+    seed_index: usize,
+    rows: u32,
+    cols: u32,
+    size: u32,
+    margin: u32,
     gravel: Vec<Stone>
 }
 
+// The controls that 0 puts back to their defaults. model() starts from the same
+// values, so there is only one place to change a default.
+#[derive(Debug)]
+struct Settings {
+    target_disp_adj: f32,
+    target_rot_adj: f32,
+    smoothing: bool,
+    fade_length: u32,
+    rot_snap_degrees: f32,
+    max_offset_x: f32,
+    max_offset_y: f32,
+    pin_corners: bool,
+    pin_edges: bool,
+    breathe_amount: f32,
+    breathe_speed: f32,
+    auto_reseed_secs: Option<f32>,
+    show_locks: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            target_disp_adj: 1.0,
+            target_rot_adj: 1.0,
+            smoothing: false,
+            fade_length: 30,
+            rot_snap_degrees: 0.0,
+            max_offset_x: f32::INFINITY,
+            max_offset_y: f32::INFINITY,
+            pin_corners: false,
+            pin_edges: false,
+            breathe_amount: 0.0,
+            breathe_speed: 1.0,
+            auto_reseed_secs: None,
+            show_locks: true,
+        }
+    }
+}
+
 fn model(app: &App) -> Model {
+    // The grid can be set up from the command line, e.g. --rows 30 --cols 20 --size 20 --margin 10
+    let grid_arg = |name, default: u32| arg_value(name).and_then(|value| value.parse().ok()).unwrap_or(default);
+    let rows = grid_arg("--rows", ROWS).max(1);
+    let cols = grid_arg("--cols", COLS).max(1);
+    let size = grid_arg("--size", SIZE).max(1);
+    let margin = grid_arg("--margin", MARGIN);
+    let seed_max = 1000000;
+    // --daily seeds from today's date so everyone gets the same arrangement that day
+    let daily = std::env::args().any(|arg| arg == "--daily").then(|| daily_seed(seed_max));
+    let title = match &daily {
+        Some((date, seed)) => format!("{}  daily {} (seed {})", app.exe_name().unwrap(), date, seed),
+        None => app.exe_name().unwrap(),
+    };
+
     let main_window = app.new_window()
-                .title(app.exe_name().unwrap())
-                .size(WIDTH, HEIGHT)
+                .title(title)
+                .size(cols * size + 2 * margin, rows * size + 2 * margin)
                 .view(view)
                 .key_pressed(key_pressed)
+                .mouse_pressed(mouse_pressed)
                 .build()
                 .unwrap();
 
+    // Enlarge the controls on high-DPI screens, e.g. --ui-scale 2
+    let ui_scale: f64 = arg_value("--ui-scale").and_then(|scale| scale.parse().ok()).unwrap_or(1.0);
     let ui_window = app.new_window()
                 .title(app.exe_name().unwrap() + " controls")
-                .size(300, 200)
+                .size((300.0 * ui_scale) as u32, (760.0 * ui_scale) as u32)
                 .view(ui_view)
                 .raw_event(raw_ui_event)
                 .key_pressed(key_pressed)
@@ -86,52 +184,439 @@ fn model(app: &App) -> Model {
     let mut theme = ui.theme_mut();
     theme.label_color = color::WHITE;
     theme.shape_color = color::CHARCOAL;
+    theme.font_size_large = (theme.font_size_large as f64 * ui_scale) as u32;
+    theme.font_size_medium = (theme.font_size_medium as f64 * ui_scale) as u32;
+    theme.font_size_small = (theme.font_size_small as f64 * ui_scale) as u32;
+
+    // --seed sets both seeds, as a number or a name
+    let seed = arg_value("--seed")
+        .and_then(|seed| seed.parse().ok().or_else(|| names::name_to_seed(&seed)))
+        .or_else(|| daily.map(|(_, seed)| seed));
+    let disp_seed = seed.unwrap_or_else(|| random_seed(seed_max));
+    let rot_seed = seed.unwrap_or_else(|| random_seed(seed_max));
+    let seed_hex = false;
+    let disp_seed_edit = None;
+    let seed_max_edit = None;
+    let rot_seed_edit = None;
+    let settings = Settings::default();
+    let disp_adj = settings.target_disp_adj;
+    let rot_adj = settings.target_rot_adj;
+    let transitioning = false;
+    let fade_from = None;
+    let fade_frame = 0;
+    let burst_count = 5;
+    let burst_remaining = 0;
+    let last_reseed = 0.0;
+
+    let sweep = if std::env::args().any(|arg| arg == "--sweep") {
+        app.set_loop_mode(LoopMode::refresh_sync());
+        let steps = |name| arg_value(name).and_then(|steps| steps.parse().ok()).unwrap_or(5);
+        Some(Sweep {
+            disp_steps: steps("--disp-steps"),
+            rot_steps: steps("--rot-steps"),
+            next: 0,
+            dir: app.exe_name().unwrap() + "_sweep",
+        })
+    } else {
+        None
+    };
 
-    let random_seed = random_range(0, 1000000);
-    let disp_adj = 1.0;
-    let rot_adj = 1.0;
+    let seed_list = match arg_value("--seedlist") {
+        Some(path) => load_seed_list(&path),
+        None => Vec::new(),
+    };
+    let seed_index = 0;
+    // Start at the top of the list rather than on random seeds
+    let (disp_seed, rot_seed) = seed_list.first().copied().unwrap_or((disp_seed, rot_seed));
+
+    let removed = HashSet::new();
+    let removed_order = Vec::new();
 
     let mut gravel = Vec::new();
-    for y in 0..ROWS {
-        for x in 0..COLS {
+    for y in 0..rows {
+        for x in 0..cols {
             let stone = Stone::new(x as f32, y as f32);
             gravel.push(stone);
         }
     }
 
+    // The controls can be hidden with H and brought back the same way
+    let ui_visible = true;
+
     Model {
         ui,
         ids,
+        ui_scale,
         main_window,
-        random_seed,
+        ui_window,
+        ui_visible,
+        disp_seed,
+        rot_seed,
+        seed_hex,
+        disp_seed_edit,
+        rot_seed_edit,
+        seed_max,
+        seed_max_edit,
         disp_adj,
         rot_adj,
+        settings,
+        transitioning,
+        fade_from,
+        fade_frame,
+        burst_count,
+        burst_remaining,
+        last_reseed,
+        sweep,
+        removed,
+        removed_order,
+        seed_list,
+        seed_index,
+        rows,
+        cols,
+        size,
+        margin,
         gravel,
     }
 }
 
-fn update(_app: &App, model: &mut Model, _update: Update) {
+fn update(app: &App, model: &mut Model, _update: Update) {
     update_ui(model);
-    let mut rng = StdRng::seed_from_u64(model.random_seed);
+    if model.settings.smoothing {
+        model.disp_adj = ease_toward(model.disp_adj, model.settings.target_disp_adj);
+        model.rot_adj = ease_toward(model.rot_adj, model.settings.target_rot_adj);
+    } else {
+        model.disp_adj = model.settings.target_disp_adj;
+        model.rot_adj = model.settings.target_rot_adj;
+    }
+
+    // A self-running display moves on to new seeds every auto_reseed_secs; while
+    // that's off the timer just keeps up so it starts counting when turned on
+    match model.settings.auto_reseed_secs {
+        Some(secs) if app.time - model.last_reseed >= secs => {
+            randomize(model);
+            model.last_reseed = app.time;
+        }
+        Some(_) => {}
+        None => model.last_reseed = app.time,
+    }
+
+    // Keep updating every frame until the transition, burst or sweep finishes, or for
+    // as long as the grid breathes or reseeds itself
+    let transitioning = model.disp_adj != model.settings.target_disp_adj
+        || model.rot_adj != model.settings.target_rot_adj
+        || model.fade_from.is_some()
+        || model.burst_remaining > 0
+        || model.sweep.is_some()
+        || model.settings.breathe_amount > 0.0
+        || model.settings.auto_reseed_secs.is_some();
+    if transitioning != model.transitioning {
+        if transitioning {
+            app.set_loop_mode(LoopMode::refresh_sync());
+        } else {
+            app.set_loop_mode(LoopMode::wait());
+        }
+        model.transitioning = transitioning;
+    }
+
+    if model.sweep.is_some() {
+        step_sweep(app, model);
+    }
+    if model.burst_remaining > 0 {
+        step_burst(app, model);
+    }
+
+    place_stones(&mut model.gravel, model.rows, model.disp_seed, model.rot_seed, model.disp_adj, model.rot_adj);
+    snap_rotations(&mut model.gravel, model.settings.rot_snap_degrees);
+    clamp_offsets(&mut model.gravel, model.settings.max_offset_x, model.settings.max_offset_y);
+
+    // Blend from the arrangement of the previous seeds to the new one
+    if let Some((disp_seed, rot_seed)) = model.fade_from {
+        let mut old = model.gravel.clone();
+        place_stones(&mut old, model.rows, disp_seed, rot_seed, model.disp_adj, model.rot_adj);
+        snap_rotations(&mut old, model.settings.rot_snap_degrees);
+        clamp_offsets(&mut old, model.settings.max_offset_x, model.settings.max_offset_y);
+        model.fade_frame += 1;
+        let t = (model.fade_frame as f32 / model.settings.fade_length as f32).min(1.0);
+        for (stone, old) in model.gravel.iter_mut().zip(&old) {
+            stone.x_offset = old.x_offset + (stone.x_offset - old.x_offset) * t;
+            stone.y_offset = old.y_offset + (stone.y_offset - old.y_offset) * t;
+            stone.rotation = old.rotation + (stone.rotation - old.rotation) * t;
+        }
+        if model.fade_frame >= model.settings.fade_length {
+            model.fade_from = None;
+        }
+    }
+
+    pin_stones(model);
+}
+
+// Hold the corner stones, or with pin_edges the whole border, still on the grid.
+fn pin_stones(model: &mut Model) {
+    if !model.settings.pin_corners && !model.settings.pin_edges {
+        return;
+    }
+    let last_x = (model.cols - 1) as f32;
+    let last_y = (model.rows - 1) as f32;
     for stone in &mut model.gravel {
-        let factor = stone.y / ROWS as f32;
-        let disp_factor = factor * model.disp_adj;
-        let rot_factor = factor * model.rot_adj;
-        stone.x_offset = disp_factor * rng.gen_range(-0.5..0.5);
-        stone.y_offset = disp_factor * rng.gen_range(-0.5..0.5);
-        stone.rotation = rot_factor * rng.gen_range(-PI / 4.0..PI / 4.0);
+        let side = stone.x == 0.0 || stone.x == last_x;
+        let end = stone.y == 0.0 || stone.y == last_y;
+        let pinned = if model.settings.pin_edges { side || end } else { side && end };
+        if pinned {
+            stone.x_offset = 0.0;
+            stone.y_offset = 0.0;
+            stone.rotation = 0.0;
+        }
     }
 }
 
+// Round each unlocked stone's rotation to the nearest multiple of `degrees`; zero
+// leaves the rotations continuous.
+fn snap_rotations(gravel: &mut [Stone], degrees: f32) {
+    if degrees <= 0.0 {
+        return;
+    }
+    let step = deg_to_rad(degrees);
+    for stone in gravel.iter_mut().filter(|stone| !stone.locked) {
+        stone.rotation = (stone.rotation / step).round() * step;
+    }
+}
+
+// Keep each unlocked stone's offset within max_x and max_y cells of home on each
+// axis; infinity leaves that axis free.
+fn clamp_offsets(gravel: &mut [Stone], max_x: f32, max_y: f32) {
+    for stone in gravel.iter_mut().filter(|stone| !stone.locked) {
+        stone.x_offset = stone.x_offset.clamp(-max_x, max_x);
+        stone.y_offset = stone.y_offset.clamp(-max_y, max_y);
+    }
+}
+
+// Value following a command line flag, e.g. arg_value("--disp-steps") for "--disp-steps 4"
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).cloned()
+}
+
+// Today's date as YYYY-MM-DD and a seed hashed from it. FNV-1a is used rather than
+// the standard hasher so the seed for a date never changes between builds.
+fn daily_seed(seed_max: u64) -> (String, u64) {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    let date = format!("{:04}-{:02}-{:02}", year, month, day);
+    let hash = date.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    (date, hash % (seed_max + 1))
+}
+
+// Read seeds from a file, one line per entry: either a single seed used for both
+// displacement and rotation, or a displacement seed and a rotation seed.
+// Lines that don't parse are skipped.
+fn load_seed_list(path: &str) -> Vec<(u64, u64)> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(error) => {
+            eprintln!("Problem reading {:?}: {}", path, error);
+            return Vec::new();
+        }
+    };
+    let mut seeds = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            [seed] => {
+                if let Ok(seed) = seed.parse() {
+                    seeds.push((seed, seed));
+                }
+            }
+            [disp_seed, rot_seed] => {
+                if let (Ok(disp_seed), Ok(rot_seed)) = (disp_seed.parse(), rot_seed.parse()) {
+                    seeds.push((disp_seed, rot_seed));
+                }
+            }
+            _ => {}
+        }
+    }
+    seeds
+}
+
+// Apply the current entry of the seed list and show where we are in the title.
+fn apply_seed_list(app: &App, model: &mut Model) {
+    let (disp_seed, rot_seed) = model.seed_list[model.seed_index];
+    model.disp_seed = disp_seed;
+    model.rot_seed = rot_seed;
+    if let Some(window) = app.window(model.main_window) {
+        window.set_title(&format!("{}  seed {}/{}  {} {}",
+            app.exe_name().unwrap(),
+            model.seed_index + 1,
+            model.seed_list.len(),
+            disp_seed,
+            rot_seed));
+    }
+}
+
+// Set the parameters for the next combination in the sweep and capture it,
+// quitting once they have all been captured.
+fn step_sweep(app: &App, model: &mut Model) {
+    let sweep = model.sweep.as_mut().unwrap();
+    let total = sweep.disp_steps * sweep.rot_steps;
+    let index = sweep.next;
+    sweep.next += 1;
+    if index >= total {
+        // Give the last capture a couple of frames to finish
+        if index > total + 2 {
+            app.quit();
+        }
+        return;
+    }
+    if index == 0 {
+        fs::create_dir_all(&sweep.dir).unwrap_or_else(|error| {
+            panic!("Problem creating directory {:?}: {}", sweep.dir, error);
+        });
+    }
+
+    let disp_adj = sweep_value(index / sweep.rot_steps, sweep.disp_steps);
+    let rot_adj = sweep_value(index % sweep.rot_steps, sweep.rot_steps);
+    let filename = format!("{}/seed{}-{}_disp{:.2}_rot{:.2}.png",
+        sweep.dir,
+        model.disp_seed,
+        model.rot_seed,
+        disp_adj,
+        rot_adj);
+    model.disp_adj = disp_adj;
+    model.rot_adj = rot_adj;
+    model.settings.target_disp_adj = disp_adj;
+    model.settings.target_rot_adj = rot_adj;
+    if let Some(window) = app.window(model.main_window) {
+        window.capture_frame(filename);
+    }
+}
+
+// Move to fresh seeds and capture them, once per update until the burst is done.
+fn step_burst(app: &App, model: &mut Model) {
+    model.burst_remaining -= 1;
+    model.disp_seed = random_seed(model.seed_max);
+    model.rot_seed = random_seed(model.seed_max);
+    model.fade_from = None;
+    let filename = format!("{}_seed{}-{}.png",
+        app.exe_name().unwrap(),
+        model.disp_seed,
+        model.rot_seed);
+    if let Some(window) = app.window(model.main_window) {
+        window.capture_frame(filename);
+    }
+}
+
+fn sweep_value(step: u32, steps: u32) -> f32 {
+    if steps > 1 {
+        SWEEP_MAX * step as f32 / (steps - 1) as f32
+    } else {
+        0.0
+    }
+}
+
+// Move part of the way from value to target, snapping to it when close.
+fn ease_toward(value: f32, target: f32) -> f32 {
+    let next = value + (target - value) * 0.2;
+    if (target - next).abs() < 0.001 {
+        target
+    } else {
+        next
+    }
+}
+
+// Write the settings behind a capture next to it so it can be recreated later.
+fn write_sidecar(model: &Model, filename: &str) {
+    let mut removed: Vec<_> = model.removed.iter().collect();
+    removed.sort();
+    let mut text = format!(
+        "disp_seed {}\nrot_seed {}\nrows {}\ncols {}\nsize {}\nmargin {}\ndisp_adj {}\nrot_adj {}\n",
+        model.disp_seed,
+        model.rot_seed,
+        model.rows,
+        model.cols,
+        model.size,
+        model.margin,
+        model.disp_adj,
+        model.rot_adj);
+    text += &settings_text(&model.settings);
+    // Locked stones keep their place whatever the seeds, so their offsets are needed too
+    for (index, stone) in model.gravel.iter().enumerate().filter(|(_, stone)| stone.locked) {
+        text += &format!("locked {} {} {} {}\n", index, stone.x_offset, stone.y_offset, stone.rotation);
+    }
+    text += &format!("removed {:?}\n", removed);
+    if let Err(error) = fs::write(filename, text) {
+        eprintln!("Problem writing {:?}: {}", filename, error);
+    }
+}
+
+// One line per setting, named after its field.
+fn settings_text(settings: &Settings) -> String {
+    let auto_reseed = settings.auto_reseed_secs.map_or("off".to_string(), |secs| secs.to_string());
+    format!(
+        "target_disp_adj {}\ntarget_rot_adj {}\nsmoothing {}\nfade_length {}\nrot_snap_degrees {}\n\
+         max_offset_x {}\nmax_offset_y {}\npin_corners {}\npin_edges {}\nbreathe_amount {}\n\
+         breathe_speed {}\nauto_reseed_secs {}\nshow_locks {}\n",
+        settings.target_disp_adj,
+        settings.target_rot_adj,
+        settings.smoothing,
+        settings.fade_length,
+        settings.rot_snap_degrees,
+        settings.max_offset_x,
+        settings.max_offset_y,
+        settings.pin_corners,
+        settings.pin_edges,
+        settings.breathe_amount,
+        settings.breathe_speed,
+        auto_reseed,
+        settings.show_locks)
+}
+
+// Put the adjustable parameters back to how the program starts; the seeds are kept.
+fn reset_defaults(model: &mut Model) {
+    model.settings = Settings::default();
+}
+
+// Move to fresh seeds, crossfading from the old arrangement if fading is on.
+fn randomize(model: &mut Model) {
+    if model.settings.fade_length > 0 {
+        model.fade_from = Some((model.disp_seed, model.rot_seed));
+        model.fade_frame = 0;
+    }
+    model.disp_seed = random_seed(model.seed_max);
+    model.rot_seed = random_seed(model.seed_max);
+}
+
+fn random_seed(seed_max: u64) -> u64 {
+    thread_rng().gen_range(0..=seed_max)
+}
+
 fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
-    let gdraw = draw.scale(SIZE as f32)
+    // The whole grid gently grows and shrinks when breathing
+    let breath = 1.0 + model.settings.breathe_amount * (app.time * model.settings.breathe_speed).sin();
+    let gdraw = draw.scale(model.size as f32 * breath)
                     .scale_y(-1.0)
-                    .x_y(COLS as f32 / -2.0 + 0.5, ROWS as f32 / -2.0 + 0.5);
+                    .x_y(model.cols as f32 / -2.0 + 0.5, model.rows as f32 / -2.0 + 0.5);
 
     draw.background().color(SNOW);
 
     for stone in &model.gravel {
+        if model.removed.contains(&(stone.x as u32, stone.y as u32)) {
+            continue;
+        }
         let cdraw = gdraw.x_y(stone.x, stone.y);
         cdraw.rect()
             .no_fill()
@@ -141,6 +626,13 @@ fn view(app: &App, model: &Model, frame: Frame) {
             .x_y(stone.x_offset, stone.y_offset)
             .rotate(stone.rotation)
             ;
+        if stone.locked && model.settings.show_locks {
+            cdraw.ellipse()
+                .color(GRAY)
+                .w_h(0.15, 0.15)
+                .x_y(stone.x_offset, stone.y_offset)
+                ;
+        }
     }
 
     draw.to_frame(app, &frame).unwrap();
@@ -148,31 +640,47 @@ fn view(app: &App, model: &Model, frame: Frame) {
 
 fn key_pressed(app: &App, model: &mut Model, key: Key) {
     match key {
-        Key::R => {
-            model.random_seed = random_range(0, 1000000);
-        }
+        Key::R => randomize(model),
         Key::S => {
             match app.window(model.main_window) {
                 Some(window) => {
                     window.capture_frame(app.exe_name().unwrap() + ".png");
+                    write_sidecar(model, &(app.exe_name().unwrap() + ".txt"));
                 }
                 None => {}
             }
         }
         Key::Up => {
-            model.disp_adj += 0.1;
+            model.settings.target_disp_adj += 0.1 * step_scale(app);
         }
         Key::Down => {
-            if model.disp_adj > 0.0 {
-                model.disp_adj -= 0.1;
-            }
+            model.settings.target_disp_adj = (model.settings.target_disp_adj - 0.1 * step_scale(app)).max(0.0);
         }
         Key::Right => {
-            model.rot_adj += 0.1;
+            model.settings.target_rot_adj += 0.1 * step_scale(app);
         }
         Key::Left => {
-            if model.rot_adj > 0.0 {
-                model.rot_adj -= 0.1;
+            model.settings.target_rot_adj = (model.settings.target_rot_adj - 0.1 * step_scale(app)).max(0.0);
+        }
+        Key::N if !model.seed_list.is_empty() => {
+            model.seed_index = (model.seed_index + 1) % model.seed_list.len();
+            apply_seed_list(app, model);
+        }
+        Key::B if !model.seed_list.is_empty() => {
+            model.seed_index = (model.seed_index + model.seed_list.len() - 1) % model.seed_list.len();
+            apply_seed_list(app, model);
+        }
+        Key::U => {
+            if let Some(cell) = model.removed_order.pop() {
+                model.removed.remove(&cell);
+            }
+        }
+        Key::M => model.burst_remaining = model.burst_count,
+        Key::Key0 => reset_defaults(model),
+        Key::H => {
+            model.ui_visible = !model.ui_visible;
+            if let Some(window) = app.window(model.ui_window) {
+                window.set_visible(model.ui_visible);
             }
         }
         _other_key => {}
@@ -180,6 +688,44 @@ fn key_pressed(app: &App, model: &mut Model, key: Key) {
 
 }
 
+// Keyboard adjustments are coarser with Shift held and finer with Ctrl held.
+fn step_scale(app: &App) -> f32 {
+    if app.keys.mods.shift() {
+        5.0
+    } else if app.keys.mods.ctrl() {
+        0.1
+    } else {
+        1.0
+    }
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if button == MouseButton::Left && app.keys.mods.shift() {
+        if let Some(index) = stone_at(model, app.mouse.position()) {
+            model.gravel[index].locked = !model.gravel[index].locked;
+        }
+    }
+    // Removed stones stay in the gravel so the others keep their random numbers
+    if button == MouseButton::Right {
+        if let Some(index) = stone_at(model, app.mouse.position()) {
+            let cell = (index as u32 % model.cols, index as u32 / model.cols);
+            if model.removed.insert(cell) {
+                model.removed_order.push(cell);
+            }
+        }
+    }
+}
+
+// Find the index of the stone whose grid cell contains a point in window coordinates.
+fn stone_at(model: &Model, point: Point2) -> Option<usize> {
+    let x = (point.x / model.size as f32 + model.cols as f32 / 2.0).floor();
+    let y = (-point.y / model.size as f32 + model.rows as f32 / 2.0).floor();
+    if x < 0.0 || y < 0.0 || x >= model.cols as f32 || y >= model.rows as f32 {
+        return None;
+    }
+    Some((y as u32 * model.cols + x as u32) as usize)
+}
+
 fn ui_view(app: &App, model: &Model, frame: Frame) {
     model.ui.draw_to_frame_if_changed(app, &frame).unwrap();
 }
@@ -188,76 +734,448 @@ fn raw_ui_event(app: &App, model: &mut Model, event: &nannou_conrod::RawWindowEv
     model.ui.handle_raw_event(app, event);
 }
 
+fn seed_text(seed: u64, hex: bool) -> String {
+    if hex {
+        format!("{:x}", seed)
+    } else {
+        seed.to_string()
+    }
+}
+
+// What a seed box shows: the text being typed while it still describes the seed,
+// otherwise the seed itself.
+fn seed_box_text(edit: &Option<(String, u64)>, seed: u64, hex: bool) -> String {
+    match edit {
+        Some((text, edit_seed)) if *edit_seed == seed => text.clone(),
+        _ => seed_text(seed, hex),
+    }
+}
+
+// Parse a seed typed into the control panel, either as a number in the current
+// radix or as its name, keeping the current seed if it is neither or is larger
+// than seed_max.
+fn parse_seed(text: &str, current: u64, hex: bool, seed_max: u64) -> u64 {
+    let radix = if hex { 16 } else { 10 };
+    u64::from_str_radix(text, radix)
+        .ok()
+        .or_else(|| names::name_to_seed(text))
+        .filter(|seed| *seed <= seed_max)
+        .unwrap_or(current)
+}
+
+// The seed maximum once the edited text is committed, keeping the current one if the
+// text isn't a number. The edit is cleared either way.
+fn commit_seed_max(edit: &mut Option<String>, current: u64) -> u64 {
+    edit.take().and_then(|text| text.trim().parse().ok()).unwrap_or(current)
+}
+
 fn update_ui(model: &mut Model) {
+    let scale = model.ui_scale;
     let ui = &mut model.ui.set_widgets();
 
     // Control panel title
     widget::Text::new("Schotter Control Panel")
-        .top_left_with_margin(10.0)
-        .w_h(300.0, 40.0)
-        .font_size(24)
+        .top_left_with_margin(10.0 * scale)
+        .w_h(300.0 * scale, 40.0 * scale)
+        .font_size((24.0 * scale) as u32)
         .set(model.ids.title, ui);
 
     // Displacement label
     widget::Text::new("Displacement")
-        .down_from(model.ids.title, 15.0)
-        .w_h(125.0, 30.0)
+        .down_from(model.ids.title, 15.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
         .set(model.ids.disp_label, ui);
 
     // Displacement slider
-    for value in widget::Slider::new(model.disp_adj, 0.0, 5.0)
-        .right_from(model.ids.disp_label, 10.0)
-        .w_h(150.0, 30.0)
-        .label(&model.disp_adj.to_string())
+    for value in widget::Slider::new(model.settings.target_disp_adj, 0.0, 5.0)
+        .right_from(model.ids.disp_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.target_disp_adj.to_string())
         .set(model.ids.disp_slider, ui)
     {
-        model.disp_adj = value;
+        model.settings.target_disp_adj = value;
     }
 
     // Rotation label
     widget::Text::new("Rotation")
-        .down_from(model.ids.disp_label, 10.00)
-        .w_h(125.0, 30.0)
+        .down_from(model.ids.disp_label, 10.00 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
         .set(model.ids.rot_label, ui);
 
     // Rotation slider
-    for value in widget::Slider::new(model.rot_adj, 0.0, 5.0)
-        .right_from(model.ids.rot_label, 10.0)
-        .w_h(150.0, 30.0)
-        .label(&model.rot_adj.to_string())
+    for value in widget::Slider::new(model.settings.target_rot_adj, 0.0, 5.0)
+        .right_from(model.ids.rot_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.target_rot_adj.to_string())
         .set(model.ids.rot_slider, ui)
     {
-        model.rot_adj = value;
+        model.settings.target_rot_adj = value;
     }
 
     // Randomize button
     for _click in widget::Button::new()
-        .down_from(model.ids.rot_label, 15.0)
-        .w_h(125.0, 40.0)
+        .down_from(model.ids.rot_label, 15.0 * scale)
+        .w_h(125.0 * scale, 40.0 * scale)
         .label("Randomize")
         .set(model.ids.randomize, ui)
     {
-        model.random_seed = random_range(0, 1000000);
+        if model.settings.fade_length > 0 {
+            model.fade_from = Some((model.disp_seed, model.rot_seed));
+            model.fade_frame = 0;
+        }
+        model.disp_seed = random_seed(model.seed_max);
+        model.rot_seed = random_seed(model.seed_max);
     }
 
-    // Seed label
-    widget::Text::new("Seed")
-        .right_from(model.ids.randomize, 10.0)
-        .w_h(40.0, 30.0)
-        .set(model.ids.seed_label, ui);
+    // Displacement seed button
+    for _click in widget::Button::new()
+        .down_from(model.ids.randomize, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Disp Seed")
+        .set(model.ids.disp_seed_button, ui)
+    {
+        model.disp_seed = random_seed(model.seed_max);
+    }
+
+    // Displacement seed text
+    for event in widget::TextBox::new(&seed_box_text(&model.disp_seed_edit, model.disp_seed, model.seed_hex))
+        .right_from(model.ids.disp_seed_button, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .set(model.ids.disp_seed_text, ui)
+    {
+        use nannou_conrod::widget::text_box::Event;
+        match event {
+            Event::Update(seed) => {
+                model.disp_seed = parse_seed(&seed, model.disp_seed, model.seed_hex, model.seed_max);
+                // Keep the partial text so a name can be typed a letter at a time
+                model.disp_seed_edit = Some((seed, model.disp_seed));
+            }
+            Event::Enter => model.disp_seed_edit = None,
+        }
+    }
+
+    // Rotation seed button
+    for _click in widget::Button::new()
+        .down_from(model.ids.disp_seed_button, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Rot Seed")
+        .set(model.ids.rot_seed_button, ui)
+    {
+        model.rot_seed = random_seed(model.seed_max);
+    }
 
-    // Seed text
-    for event in widget::TextBox::new(&model.random_seed.to_string())
-        .right_from(model.ids.seed_label, 10.0)
-        .w_h(100.0, 30.0)
-        .set(model.ids.seed_text, ui)
+    // Rotation seed text
+    for event in widget::TextBox::new(&seed_box_text(&model.rot_seed_edit, model.rot_seed, model.seed_hex))
+        .right_from(model.ids.rot_seed_button, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .set(model.ids.rot_seed_text, ui)
     {
         use nannou_conrod::widget::text_box::Event;
         match event {
             Event::Update(seed) => {
-                model.random_seed = seed.parse().unwrap_or(0);
+                model.rot_seed = parse_seed(&seed, model.rot_seed, model.seed_hex, model.seed_max);
+                model.rot_seed_edit = Some((seed, model.rot_seed));
+            }
+            Event::Enter => model.rot_seed_edit = None,
+        }
+    }
+
+    // Seed names
+    widget::Text::new(&format!("{}  {}",
+            names::seed_to_name(model.disp_seed),
+            names::seed_to_name(model.rot_seed)))
+        .down_from(model.ids.rot_seed_button, 10.0 * scale)
+        .w_h(285.0 * scale, 30.0 * scale)
+        .set(model.ids.seed_names, ui);
+
+    // Hexadecimal seed toggle
+    for value in widget::Toggle::new(model.seed_hex)
+        .down_from(model.ids.seed_names, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Hex Seed")
+        .set(model.ids.hex_toggle, ui)
+    {
+        model.seed_hex = value;
+        model.disp_seed_edit = None;
+        model.rot_seed_edit = None;
+    }
+
+    // Show locked stones toggle
+    for value in widget::Toggle::new(model.settings.show_locks)
+        .right_from(model.ids.hex_toggle, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label("Show Locks")
+        .set(model.ids.locks_toggle, ui)
+    {
+        model.settings.show_locks = value;
+    }
+
+    // Seed maximum label
+    widget::Text::new("Seed Max")
+        .down_from(model.ids.hex_toggle, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.seed_max_label, ui);
+
+    // Seed maximum text
+    let seed_max_text = model.seed_max_edit.clone().unwrap_or_else(|| model.seed_max.to_string());
+    for event in widget::TextBox::new(&seed_max_text)
+        .right_from(model.ids.seed_max_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .set(model.ids.seed_max_text, ui)
+    {
+        use nannou_conrod::widget::text_box::Event;
+        match event {
+            // Hold the text until Enter so a half-typed number doesn't change the maximum
+            Event::Update(text) => model.seed_max_edit = Some(text),
+            Event::Enter => model.seed_max = commit_seed_max(&mut model.seed_max_edit, model.seed_max),
+        }
+    }
+    // Clicking elsewhere commits the edit too. The keyboard goes to the text edit inside the box.
+    let seed_max_focused = ui.global_input().current.widget_capturing_keyboard
+        .is_some_and(|id| ui.widget_graph().depth_parent(id) == Some(model.ids.seed_max_text));
+    if model.seed_max_edit.is_some() && !seed_max_focused {
+        model.seed_max = commit_seed_max(&mut model.seed_max_edit, model.seed_max);
+    }
+
+    // Smooth transitions toggle
+    for value in widget::Toggle::new(model.settings.smoothing)
+        .down_from(model.ids.seed_max_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Smooth")
+        .set(model.ids.smooth_toggle, ui)
+    {
+        model.settings.smoothing = value;
+    }
+
+    // Crossfade length label
+    widget::Text::new("Fade frames")
+        .down_from(model.ids.smooth_toggle, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.fade_label, ui);
+
+    // Crossfade length slider
+    if let Some(value) = widget::Slider::new(model.settings.fade_length as f32, 0.0, 120.0)
+        .right_from(model.ids.fade_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.fade_length.to_string())
+        .set(model.ids.fade_slider, ui)
+    {
+        model.settings.fade_length = value.round() as u32;
+    }
+
+    // Rotation snap label
+    widget::Text::new("Snap degrees")
+        .down_from(model.ids.fade_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.snap_label, ui);
+
+    // Rotation snap slider
+    if let Some(value) = widget::Slider::new(model.settings.rot_snap_degrees, 0.0, 45.0)
+        .right_from(model.ids.snap_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.rot_snap_degrees.to_string())
+        .set(model.ids.snap_slider, ui)
+    {
+        model.settings.rot_snap_degrees = value.round();
+    }
+
+    // Burst size label
+    widget::Text::new("Burst (M)")
+        .down_from(model.ids.snap_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.burst_label, ui);
+
+    // Burst size slider
+    if let Some(value) = widget::Slider::new(model.burst_count as f32, 1.0, 50.0)
+        .right_from(model.ids.burst_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.burst_count.to_string())
+        .set(model.ids.burst_slider, ui)
+    {
+        model.burst_count = value.round() as u32;
+    }
+
+    // Pin corners toggle
+    for value in widget::Toggle::new(model.settings.pin_corners)
+        .down_from(model.ids.burst_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Pin Corners")
+        .set(model.ids.pin_corners_toggle, ui)
+    {
+        model.settings.pin_corners = value;
+    }
+
+    // Pin edges toggle
+    for value in widget::Toggle::new(model.settings.pin_edges)
+        .right_from(model.ids.pin_corners_toggle, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label("Pin Edges")
+        .set(model.ids.pin_edges_toggle, ui)
+    {
+        model.settings.pin_edges = value;
+    }
+
+    // Breathing amount label
+    widget::Text::new("Breathe")
+        .down_from(model.ids.pin_corners_toggle, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.breathe_amount_label, ui);
+
+    // Breathing amount slider
+    if let Some(value) = widget::Slider::new(model.settings.breathe_amount, 0.0, 0.2)
+        .right_from(model.ids.breathe_amount_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&format!("{:.2}", model.settings.breathe_amount))
+        .set(model.ids.breathe_amount_slider, ui)
+    {
+        model.settings.breathe_amount = value;
+    }
+
+    // Breathing speed label
+    widget::Text::new("Breathe speed")
+        .down_from(model.ids.breathe_amount_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.breathe_speed_label, ui);
+
+    // Breathing speed slider
+    if let Some(value) = widget::Slider::new(model.settings.breathe_speed, 0.1, 5.0)
+        .right_from(model.ids.breathe_speed_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&format!("{:.1}", model.settings.breathe_speed))
+        .set(model.ids.breathe_speed_slider, ui)
+    {
+        model.settings.breathe_speed = value;
+    }
+
+    // Auto reseed interval label
+    widget::Text::new("Auto reseed")
+        .down_from(model.ids.breathe_speed_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.auto_reseed_label, ui);
+
+    // Auto reseed interval slider; all the way left turns it off
+    let auto_reseed_text = match model.settings.auto_reseed_secs {
+        Some(secs) => format!("{} s", secs),
+        None => "off".to_string(),
+    };
+    if let Some(value) = widget::Slider::new(model.settings.auto_reseed_secs.unwrap_or(0.0), 0.0, 300.0)
+        .right_from(model.ids.auto_reseed_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&auto_reseed_text)
+        .set(model.ids.auto_reseed_slider, ui)
+    {
+        let secs = value.round();
+        model.settings.auto_reseed_secs = if secs > 0.0 { Some(secs) } else { None };
+    }
+
+    // Offset limits; all the way right leaves the axis unclamped
+    let limit_text = |limit: f32| if limit.is_finite() { format!("{:.2}", limit) } else { "off".to_string() };
+    let limit_value = |value: f32| if value >= MAX_OFFSET_LIMIT { f32::INFINITY } else { value };
+
+    // Horizontal offset limit label
+    widget::Text::new("Max X offset")
+        .down_from(model.ids.auto_reseed_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.max_x_label, ui);
+
+    // Horizontal offset limit slider
+    if let Some(value) = widget::Slider::new(model.settings.max_offset_x.min(MAX_OFFSET_LIMIT), 0.0, MAX_OFFSET_LIMIT)
+        .right_from(model.ids.max_x_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&limit_text(model.settings.max_offset_x))
+        .set(model.ids.max_x_slider, ui)
+    {
+        model.settings.max_offset_x = limit_value(value);
+    }
+
+    // Vertical offset limit label
+    widget::Text::new("Max Y offset")
+        .down_from(model.ids.max_x_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.max_y_label, ui);
+
+    // Vertical offset limit slider
+    if let Some(value) = widget::Slider::new(model.settings.max_offset_y.min(MAX_OFFSET_LIMIT), 0.0, MAX_OFFSET_LIMIT)
+        .right_from(model.ids.max_y_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&limit_text(model.settings.max_offset_y))
+        .set(model.ids.max_y_slider, ui)
+    {
+        model.settings.max_offset_y = limit_value(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn place_stones_is_stable() {
+        let mut gravel = Vec::new();
+        for y in 0..ROWS {
+            for x in 0..COLS {
+                gravel.push(Stone::new(x as f32, y as f32));
             }
-            Event::Enter => {}
+        }
+        place_stones(&mut gravel, ROWS, 42, 43, 1.0, 1.0);
+
+        // The first row is never displaced
+        for stone in &gravel[0..COLS as usize] {
+            assert_eq!((stone.x_offset, stone.y_offset, stone.rotation), (0.0, 0.0, 0.0));
+        }
+
+        let expected = [
+            (COLS as usize, 0.016659103, -0.004970491, -0.01729848),
+            (COLS as usize + 1, 0.009548702, -0.016323637, -0.024531439),
+            (COLS as usize + 2, 0.0137347635, 0.001047232, 0.009861331),
+            ((ROWS * COLS) as usize - 1, 0.011741264, -0.35135296, 0.18150496),
+        ];
+        for (index, x_offset, y_offset, rotation) in expected {
+            assert_close(gravel[index].x_offset, x_offset);
+            assert_close(gravel[index].y_offset, y_offset);
+            assert_close(gravel[index].rotation, rotation);
+        }
+    }
+
+    #[test]
+    fn seed_names_round_trip() {
+        for seed in [0, 1, 63, 64, 4095, 4096, 1000000, u64::MAX] {
+            assert_eq!(names::name_to_seed(&names::seed_to_name(seed)), Some(seed));
+        }
+        assert_eq!(names::name_to_seed("Brave-Otter"), names::name_to_seed("brave-otter"));
+        assert_eq!(names::name_to_seed("brave-unicorn"), None);
+    }
+
+    #[test]
+    fn sidecar_has_every_setting() {
+        let text = settings_text(&Settings::default());
+        // Take the field names from the Debug output, so a new field is caught here
+        let debug = format!("{:?}", Settings::default());
+        let fields = debug.trim_start_matches("Settings {").trim_end_matches('}');
+        for field in fields.split(", ") {
+            let name = field.trim().split(':').next().unwrap();
+            assert!(text.lines().any(|line| line.split(' ').next() == Some(name)), "{} is missing from the sidecar", name);
+        }
+    }
+
+    #[test]
+    fn rotation_seed_leaves_displacement_alone() {
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        for y in 0..ROWS {
+            for x in 0..COLS {
+                first.push(Stone::new(x as f32, y as f32));
+                second.push(Stone::new(x as f32, y as f32));
+            }
+        }
+        place_stones(&mut first, ROWS, 42, 1, 1.0, 1.0);
+        place_stones(&mut second, ROWS, 42, 2, 1.0, 1.0);
+        for (a, b) in first.iter().zip(&second) {
+            assert_eq!((a.x_offset, a.y_offset), (b.x_offset, b.y_offset));
         }
     }
 }
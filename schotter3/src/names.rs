@@ -0,0 +1,48 @@
+// Memorable names for seeds, like "brave-otter", so an arrangement is easy to share.
+// The low 6 bits of the seed pick the adjective and the next 6 the animal; anything
+// left over is appended as a number, e.g. "brave-otter-12", so every seed has a name.
+
+const ADJECTIVES: [&str; 64] = [
+    "amber", "ancient", "bold", "brave", "bright", "brisk", "calm", "clever",
+    "cosmic", "crisp", "curious", "daring", "dusty", "eager", "early", "fancy",
+    "fierce", "gentle", "giddy", "golden", "grand", "happy", "hidden", "humble",
+    "icy", "jolly", "keen", "kind", "lively", "lucky", "mellow", "merry",
+    "misty", "modest", "noble", "odd", "plucky", "polite", "proud", "quick",
+    "quiet", "rapid", "rustic", "shy", "silent", "silver", "sleepy", "sly",
+    "smooth", "snowy", "solid", "spry", "stormy", "sunny", "swift", "tidy",
+    "tiny", "vivid", "wandering", "warm", "wild", "wise", "witty", "zesty",
+];
+
+const ANIMALS: [&str; 64] = [
+    "badger", "bear", "beaver", "bison", "camel", "cheetah", "condor", "cougar",
+    "crane", "crow", "dingo", "dolphin", "donkey", "eagle", "egret", "elk",
+    "falcon", "ferret", "finch", "fox", "gecko", "gibbon", "goose", "heron",
+    "hippo", "ibis", "jackal", "jaguar", "koala", "lemur", "lion", "llama",
+    "lynx", "magpie", "marmot", "mink", "moose", "newt", "ocelot", "orca",
+    "osprey", "otter", "owl", "panda", "parrot", "pelican", "penguin", "puffin",
+    "quail", "rabbit", "raven", "salmon", "seal", "sparrow", "stork", "swan",
+    "tapir", "tiger", "toucan", "turtle", "walrus", "weasel", "wolf", "zebra",
+];
+
+pub fn seed_to_name(seed: u64) -> String {
+    let adjective = ADJECTIVES[(seed & 63) as usize];
+    let animal = ANIMALS[(seed >> 6 & 63) as usize];
+    match seed >> 12 {
+        0 => format!("{}-{}", adjective, animal),
+        rest => format!("{}-{}-{}", adjective, animal, rest),
+    }
+}
+
+pub fn name_to_seed(name: &str) -> Option<u64> {
+    let name = name.trim().to_lowercase();
+    let parts: Vec<&str> = name.split('-').collect();
+    let (adjective, animal, rest) = match parts[..] {
+        [adjective, animal] => (adjective, animal, 0),
+        [adjective, animal, rest] => (adjective, animal, rest.parse::<u64>().ok()?),
+        _ => return None,
+    };
+    let adjective = ADJECTIVES.iter().position(|word| *word == adjective)? as u64;
+    let animal = ANIMALS.iter().position(|word| *word == animal)? as u64;
+    let high = rest.checked_mul(1 << 12)?;
+    Some(high | animal << 6 | adjective)
+}
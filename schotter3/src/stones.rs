@@ -0,0 +1,49 @@
+use nannou::prelude::*;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
+
+#[derive(Clone)]
+pub struct Stone {
+    pub x: f32,
+    pub y: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub rotation: f32,
+    pub locked: bool,
+}
+
+impl Stone {
+    pub fn new(x: f32, y: f32) -> Self {
+        let x_offset = 0.0;
+        let y_offset = 0.0;
+        let rotation = 0.0;
+        let locked = false;
+        Stone {
+            x,
+            y,
+            x_offset,
+            y_offset,
+            rotation,
+            locked,
+        }
+    }
+}
+
+// Locked stones still draw their random numbers so the others don't move
+pub fn place_stones(gravel: &mut [Stone], rows: u32, disp_seed: u64, rot_seed: u64, disp_adj: f32, rot_adj: f32) {
+    let mut disp_rng = StdRng::seed_from_u64(disp_seed);
+    let mut rot_rng = StdRng::seed_from_u64(rot_seed);
+    for stone in gravel {
+        let factor = stone.y / rows as f32;
+        let disp_factor = factor * disp_adj;
+        let rot_factor = factor * rot_adj;
+        let x_offset = disp_factor * disp_rng.gen_range(-0.5..0.5);
+        let y_offset = disp_factor * disp_rng.gen_range(-0.5..0.5);
+        let rotation = rot_factor * rot_rng.gen_range(-PI / 4.0..PI / 4.0);
+        if !stone.locked {
+            stone.x_offset = x_offset;
+            stone.y_offset = y_offset;
+            stone.rotation = rotation;
+        }
+    }
+}
@@ -38,16 +38,50 @@ impl Stone {
     }
 }
 
+// The edge the stones are orderly at, with disorder growing away from it
+#[derive(PartialEq, Clone, Copy)]
+enum FactorEdge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+impl FactorEdge {
+    // How far a stone is from the edge, from 0.0 on the edge toward 1.0 at the far side
+    fn factor(self, stone: &Stone) -> f32 {
+        match self {
+            FactorEdge::Top => stone.y / ROWS as f32,
+            FactorEdge::Bottom => (ROWS as f32 - 1.0 - stone.y) / ROWS as f32,
+            FactorEdge::Left => stone.x / COLS as f32,
+            FactorEdge::Right => (COLS as f32 - 1.0 - stone.x) / COLS as f32,
+        }
+    }
+}
+
 struct Model {
     ui: Egui,
     main_window: WindowId,
+    ui_window: WindowId,
+    ui_visible: bool,
     random_seed: u64,
     disp_adj: f32,
     rot_adj: f32,
+    factor_edge: FactorEdge,
+    rot_col_weight: f32,
     gravel: Vec<Stone>
 }
 
 fn model(app: &App) -> Model {
+    // --fixed-seed 42 starts on a known arrangement for development; without it, or
+    // with --random-seed, every launch gets a fresh one
+    let fixed_seed = arg_value("--fixed-seed").map(|seed| match seed.parse::<u64>() {
+        Ok(seed) => seed,
+        Err(error) => {
+            eprintln!("Bad --fixed-seed {:?}: {}", seed, error);
+            std::process::exit(1);
+        }
+    });
     let main_window = app.new_window()
                 .title(app.exe_name().unwrap())
                 .size(WIDTH, HEIGHT)
@@ -58,7 +92,7 @@ fn model(app: &App) -> Model {
 
     let ui_window = app.new_window()
                 .title(app.exe_name().unwrap() + " controls")
-                .size(280, 130)
+                .size(280, 178)
                 .view(ui_view)
                 .raw_event(raw_ui_event)
                 .key_pressed(key_pressed)
@@ -68,9 +102,14 @@ fn model(app: &App) -> Model {
     let ui_window_ref = app.window(ui_window).unwrap();
     let ui = Egui::from_window(&ui_window_ref);
 
-    let random_seed = random_range(0, 1000000);
+    let random_seed = match fixed_seed {
+        Some(seed) if !std::env::args().any(|arg| arg == "--random-seed") => seed,
+        _ => random_range(0, 1000000),
+    };
     let disp_adj = 1.0;
     let rot_adj = 1.0;
+    let factor_edge = FactorEdge::Top;
+    let rot_col_weight = 0.0;
 
     let mut gravel = Vec::new();
     for y in 0..ROWS {
@@ -80,12 +119,19 @@ fn model(app: &App) -> Model {
         }
     }
 
+    // The controls can be hidden with H and brought back the same way
+    let ui_visible = true;
+
     Model {
         ui,
         main_window,
+        ui_window,
+        ui_visible,
         random_seed,
         disp_adj,
         rot_adj,
+        factor_edge,
+        rot_col_weight,
         gravel,
     }
 }
@@ -94,9 +140,11 @@ fn update(_app: &App, model: &mut Model, _update: Update) {
     update_ui(model);
     let mut rng = StdRng::seed_from_u64(model.random_seed);
     for stone in &mut model.gravel {
-        let factor = stone.y / ROWS as f32;
+        let factor = model.factor_edge.factor(stone);
         let disp_factor = factor * model.disp_adj;
-        let rot_factor = factor * model.rot_adj;
+        // Rotation can also grow across the columns, independent of displacement
+        let col_factor = stone.x / COLS as f32;
+        let rot_factor = (factor * (1.0 - model.rot_col_weight) + col_factor * model.rot_col_weight) * model.rot_adj;
         stone.x_offset = disp_factor * rng.gen_range(-0.5..0.5);
         stone.y_offset = disp_factor * rng.gen_range(-0.5..0.5);
         stone.rotation = rot_factor * rng.gen_range(-PI / 4.0..PI / 4.0);
@@ -140,19 +188,21 @@ fn key_pressed(app: &App, model: &mut Model, key: Key) {
             }
         }
         Key::Up => {
-            model.disp_adj += 0.1;
+            model.disp_adj += 0.1 * step_scale(app);
         }
         Key::Down => {
-            if model.disp_adj > 0.0 {
-                model.disp_adj -= 0.1;
-            }
+            model.disp_adj = (model.disp_adj - 0.1 * step_scale(app)).max(0.0);
         }
         Key::Right => {
-            model.rot_adj += 0.1;
+            model.rot_adj += 0.1 * step_scale(app);
         }
         Key::Left => {
-            if model.rot_adj > 0.0 {
-                model.rot_adj -= 0.1;
+            model.rot_adj = (model.rot_adj - 0.1 * step_scale(app)).max(0.0);
+        }
+        Key::H => {
+            model.ui_visible = !model.ui_visible;
+            if let Some(window) = app.window(model.ui_window) {
+                window.set_visible(model.ui_visible);
             }
         }
         _other_key => {}
@@ -160,6 +210,24 @@ fn key_pressed(app: &App, model: &mut Model, key: Key) {
 
 }
 
+// Value following a command line flag, e.g. arg_value("--fixed-seed") for "--fixed-seed 42"
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).cloned()
+}
+
+// Keyboard adjustments are coarser with Shift held and finer with Ctrl held.
+fn step_scale(app: &App) -> f32 {
+    if app.keys.mods.shift() {
+        5.0
+    } else if app.keys.mods.ctrl() {
+        0.1
+    } else {
+        1.0
+    }
+}
+
 fn ui_view(_app: &App, model: &Model, frame: Frame) {
     model.ui.draw_to_frame(&frame).unwrap();
 }
@@ -173,6 +241,7 @@ fn update_ui(model: &mut Model) {
     egui::Window::new("Schotter Control Panel").collapsible(false).show(&ctx, |ui| {
         ui.add(egui::Slider::new(&mut model.disp_adj, 0.0..=5.0).text("Displacement"));
         ui.add(egui::Slider::new(&mut model.rot_adj, 0.0..=5.0).text("Rotation"));
+        ui.add(egui::Slider::new(&mut model.rot_col_weight, 0.0..=1.0).text("Rotation by column"));
         ui.horizontal(|ui| {
             if ui.add(egui::Button::new("Randomize")).clicked() {
                 model.random_seed = random_range(0, 1000000);
@@ -181,5 +250,12 @@ fn update_ui(model: &mut Model) {
             ui.add(egui::DragValue::new(&mut model.random_seed));
             ui.label("Seed");
         });
+        ui.horizontal(|ui| {
+            ui.label("Calm edge");
+            ui.radio_value(&mut model.factor_edge, FactorEdge::Top, "Top");
+            ui.radio_value(&mut model.factor_edge, FactorEdge::Bottom, "Bottom");
+            ui.radio_value(&mut model.factor_edge, FactorEdge::Left, "Left");
+            ui.radio_value(&mut model.factor_edge, FactorEdge::Right, "Right");
+        });
     });
 }
@@ -1,4 +1,6 @@
 use nannou::prelude::*;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
 use nannou_conrod::prelude::*;
 use std::fs;
 use std::io::ErrorKind;
@@ -10,6 +12,9 @@ const LINE_WIDTH: f32 = 0.06;
 const MARGIN: u32 = 35;
 const WIDTH: u32 = COLS * SIZE + 2 * MARGIN;
 const HEIGHT: u32 = ROWS * SIZE + 2 * MARGIN;
+const PAN_STEP: f32 = 10.0;
+const RESTING_ALPHA: f32 = 0.15;
+const GRID_CYCLES: u32 = 60;
 
 fn main() {
     nannou::app(model).update(update).loop_mode(LoopMode::refresh_sync()).run()
@@ -24,6 +29,21 @@ widget_ids! {
         rot_slider,
         motion_label,
         motion_slider,
+        cycle_min_label,
+        cycle_min_slider,
+        cycle_max_label,
+        cycle_max_slider,
+        fade_toggle,
+        ellipse_toggle,
+        aspect_label,
+        aspect_slider,
+        rows_label,
+        rows_slider,
+        cols_label,
+        cols_slider,
+        clear_toggle,
+        scale_dip_label,
+        scale_dip_slider,
     }
 }
 
@@ -37,6 +57,7 @@ struct Stone {
     y_velocity: f32,
     rot_velocity: f32,
     cycles: u32,
+    total_cycles: u32,
 }
 
 impl Stone {
@@ -48,6 +69,7 @@ impl Stone {
         let y_velocity = 0.0;
         let rot_velocity = 0.0;
         let cycles = 0;
+        let total_cycles = 0;
         Stone {
             x,
             y,
@@ -58,6 +80,7 @@ impl Stone {
             y_velocity,
             rot_velocity,
             cycles,
+            total_cycles,
         }
     }
 }
@@ -65,14 +88,68 @@ impl Stone {
 struct Model {
     ui: Ui,
     ids: Ids,
+    ui_scale: f64,
     main_window: WindowId,
+    ui_window: WindowId,
+    ui_visible: bool,
     frames_dir: String,
     cur_frame: u32,
     recording: bool,
+    max_frames: u32,
+    frame_digits: usize,
+    capturing: bool,
+    screenshot_requested: bool,
+    clock_in_captures: bool,
+    pan: Vec2,
+    paused: bool,
+    single_step: bool,
+    steps: u64,
+    random_seed: u64,
+    rng: StdRng,
+    lock_seed: bool,
+    settings: Settings,
+    clear_requested: bool,
+    clearing: bool,
+    rows: u32,
+    cols: u32,
+    gravel: Vec<Stone>,
+    leaving: Vec<Stone>,
+}
+
+// The controls that 0 puts back to their defaults. model() starts from the same
+// values, so there is only one place to change a default.
+struct Settings {
     disp_adj: f32,
     rot_adj: f32,
     motion: f32,
-    gravel: Vec<Stone>
+    cycle_min: u32,
+    cycle_max: u32,
+    fade_at_rest: bool,
+    scale_dip: f32,
+    clear_each_frame: bool,
+    elliptical: bool,
+    aspect: f32,
+    target_rows: u32,
+    target_cols: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            disp_adj: 1.0,
+            rot_adj: 1.0,
+            motion: 0.5,
+            cycle_min: 50,
+            cycle_max: 300,
+            fade_at_rest: false,
+            scale_dip: 0.0,
+            clear_each_frame: true,
+            elliptical: false,
+            aspect: 1.0,
+            target_rows: ROWS,
+            target_cols: COLS,
+        }
+    }
 }
 
 fn model(app: &App) -> Model {
@@ -84,9 +161,11 @@ fn model(app: &App) -> Model {
                 .build()
                 .unwrap();
 
+    // Enlarge the controls on high-DPI screens, e.g. --ui-scale 2
+    let ui_scale: f64 = arg_value("--ui-scale").and_then(|scale| scale.parse().ok()).unwrap_or(1.0);
     let ui_window = app.new_window()
                 .title(app.exe_name().unwrap() + " controls")
-                .size(300, 200)
+                .size((300.0 * ui_scale) as u32, (560.0 * ui_scale) as u32)
                 .view(ui_view)
                 .raw_event(raw_ui_event)
                 .key_pressed(key_pressed)
@@ -100,58 +179,148 @@ fn model(app: &App) -> Model {
     let mut theme = ui.theme_mut();
     theme.label_color = color::WHITE;
     theme.shape_color = color::CHARCOAL;
+    theme.font_size_large = (theme.font_size_large as f64 * ui_scale) as u32;
+    theme.font_size_medium = (theme.font_size_medium as f64 * ui_scale) as u32;
+    theme.font_size_small = (theme.font_size_small as f64 * ui_scale) as u32;
 
     let frames_dir = app.exe_name().unwrap() + "_frames";
     let recording = false;
     let cur_frame = 0;
-
-    let disp_adj = 1.0;
-    let rot_adj = 1.0;
-    let motion = 0.5;
+    // --max-frames 100000 allows longer recordings
+    let max_frames = arg_value("--max-frames").and_then(|frames| frames.parse().ok()).unwrap_or(9999);
+    let frame_digits = 4;
+    let capturing = false;
+    let screenshot_requested = false;
+    let clock_in_captures = false;
+    let pan = vec2(0.0, 0.0);
+    let paused = false;
+    let single_step = false;
+    let steps = 0;
+    // --seed 42 picks the motion that L locks in; otherwise it is random
+    let random_seed = arg_value("--seed").and_then(|seed| seed.parse().ok()).unwrap_or_else(random::<u64>);
+    let rng = StdRng::seed_from_u64(random_seed);
+    let lock_seed = false;
+
+    let settings = Settings::default();
+    let clear_requested = false;
+    let clearing = false;
+    let rows = ROWS;
+    let cols = COLS;
 
     let mut gravel = Vec::new();
-    for y in 0..ROWS {
-        for x in 0..COLS {
+    for y in 0..rows {
+        for x in 0..cols {
             let stone = Stone::new(x as f32, y as f32);
             gravel.push(stone);
         }
     }
+    let leaving = Vec::new();
+
+    // The controls can be hidden with H and brought back the same way
+    let ui_visible = true;
 
     Model {
         ui,
         ids,
+        ui_scale,
         main_window,
+        ui_window,
+        ui_visible,
         frames_dir,
         recording,
         cur_frame,
-        disp_adj,
-        rot_adj,
-        motion,
+        max_frames,
+        frame_digits,
+        capturing,
+        screenshot_requested,
+        clock_in_captures,
+        pan,
+        paused,
+        single_step,
+        steps,
+        random_seed,
+        rng,
+        lock_seed,
+        settings,
+        clear_requested,
+        clearing,
+        rows,
+        cols,
         gravel,
+        leaving,
     }
 }
 
 fn update(app: &App, model: &mut Model, _update: Update) {
     update_ui(model);
+    if (model.settings.target_rows, model.settings.target_cols) != (model.rows, model.cols) {
+        resize_grid(app, model);
+    }
+    if !model.paused || model.single_step {
+        move_stones(model);
+        model.steps += 1;
+        model.single_step = false;
+    }
+
+    model.capturing = false;
+    // Take the screenshot here, so the frame it captures knows to leave out the overlays
+    if model.screenshot_requested {
+        model.screenshot_requested = false;
+        if let Some(window) = app.window(model.main_window) {
+            window.capture_frame(app.exe_name().unwrap() + ".png");
+            model.capturing = true;
+        }
+    }
+    // A requested clear applies to the frame drawn after this update
+    model.clearing = model.clear_requested;
+    model.clear_requested = false;
+    if model.recording && app.elapsed_frames() % 2 == 0 {
+        model.cur_frame += 1;
+        if model.cur_frame > model.max_frames {
+            model.recording = false;
+        } else {
+            let filename = format!("{}/schotter{:0width$}.png",
+                model.frames_dir,
+                model.cur_frame,
+                width = model.frame_digits);
+            match app.window(model.main_window) {
+                Some(window) => {
+                    window.capture_frame(filename);
+                    model.capturing = true;
+                }
+                None => {}
+            }
+        }
+    }
+}
+
+fn move_stones(model: &mut Model) {
     for stone in &mut model.gravel {
         if stone.cycles == 0 {
-            if random_f32() > model.motion {
+            if model.rng.gen::<f32>() > model.settings.motion {
                 stone.x_velocity = 0.0;
                 stone.y_velocity = 0.0;
                 stone.rot_velocity = 0.0;
-                stone.cycles = random_range(50, 300);
+                stone.cycles = model.rng.gen_range(model.settings.cycle_min..model.settings.cycle_max);
+                stone.total_cycles = stone.cycles;
             } else {
-                let factor = stone.y / ROWS as f32;
-                let disp_factor = factor * model.disp_adj;
-                let rot_factor = factor * model.rot_adj;
-                let new_x = disp_factor * random_range(-0.5, 0.5);
-                let new_y = disp_factor * random_range(-0.5, 0.5);
-                let new_rot = rot_factor * random_range(-PI / 4.0, PI / 4.0);
-                let new_cycles = random_range(50, 300);
+                let factor = stone.y / model.rows as f32;
+                let disp_factor = factor * model.settings.disp_adj;
+                let rot_factor = factor * model.settings.rot_adj;
+                let new_x = disp_factor * model.rng.gen_range(-0.5..0.5);
+                let new_y = disp_factor * model.rng.gen_range(-0.5..0.5);
+                let (new_x, new_y) = if model.settings.elliptical {
+                    ellipse_bound(new_x, new_y, disp_factor * 0.5, model.settings.aspect)
+                } else {
+                    (new_x, new_y)
+                };
+                let new_rot = rot_factor * model.rng.gen_range(-PI / 4.0..PI / 4.0);
+                let new_cycles = model.rng.gen_range(model.settings.cycle_min..model.settings.cycle_max);
                 stone.x_velocity = (new_x - stone.x_offset) / new_cycles as f32;
                 stone.y_velocity = (new_y - stone.y_offset) / new_cycles as f32;
                 stone.rot_velocity = (new_rot - stone.rotation) / new_cycles as f32;
                 stone.cycles = new_cycles;
+                stone.total_cycles = new_cycles;
             }
         } else {
             stone.x_offset += stone.x_velocity;
@@ -161,56 +330,211 @@ fn update(app: &App, model: &mut Model, _update: Update) {
         }
     }
 
-    if model.recording && app.elapsed_frames() % 2 == 0 {
-        model.cur_frame += 1;
-        if model.cur_frame > 9999 {
-            model.recording = false;
-        } else {
-            let filename = format!("{}/schotter{:>04}.png",
-                model.frames_dir,
-                model.cur_frame);
-            match app.window(model.main_window) {
-                Some(window) => {
-                    window.capture_frame(filename);
-                }
-                None => {}
-            }
+    // Stones dropped from the grid drift away and are forgotten once out of sight
+    for stone in &mut model.leaving {
+        stone.x_offset += stone.x_velocity;
+        stone.y_offset += stone.y_velocity;
+        stone.cycles -= 1;
+    }
+    model.leaving.retain(|stone| stone.cycles > 0);
+}
+
+// Rebuild the gravel for the target grid size. Stones still inside the grid are
+// kept; new ones fly in from beyond the window and removed ones fly out.
+fn resize_grid(app: &App, model: &mut Model) {
+    let (old_rows, old_cols) = (model.rows, model.cols);
+    let (rows, cols) = (model.settings.target_rows, model.settings.target_cols);
+    let mut old: Vec<Option<Stone>> = std::mem::take(&mut model.gravel).into_iter().map(Some).collect();
+    model.rows = rows;
+    model.cols = cols;
+    let rect = app.window(model.main_window).map_or(Rect::from_w_h(WIDTH as f32, HEIGHT as f32), |window| window.rect());
+
+    for y in 0..rows {
+        for x in 0..cols {
+            let stone = if x < old_cols && y < old_rows {
+                old[(y * old_cols + x) as usize].take().unwrap()
+            } else {
+                let mut stone = Stone::new(x as f32, y as f32);
+                let start = offscreen_offset(model, &stone, rect);
+                stone.x_offset = start.x;
+                stone.y_offset = start.y;
+                stone.x_velocity = -start.x / GRID_CYCLES as f32;
+                stone.y_velocity = -start.y / GRID_CYCLES as f32;
+                stone.cycles = GRID_CYCLES;
+                stone.total_cycles = GRID_CYCLES;
+                stone
+            };
+            model.gravel.push(stone);
         }
     }
+
+    for mut stone in old.into_iter().flatten() {
+        let away = offscreen_offset(model, &stone, rect);
+        stone.x_velocity = away.x / GRID_CYCLES as f32;
+        stone.y_velocity = away.y / GRID_CYCLES as f32;
+        stone.rot_velocity = 0.0;
+        stone.cycles = GRID_CYCLES;
+        stone.total_cycles = GRID_CYCLES;
+        model.leaving.push(stone);
+    }
+}
+
+// With the seed locked, send the stones back to the grid and start the random stream
+// over from random_seed so the same motion plays again.
+fn restart_motion(model: &mut Model) {
+    model.rng = StdRng::seed_from_u64(model.random_seed);
+    for stone in &mut model.gravel {
+        *stone = Stone::new(stone.x, stone.y);
+    }
+    model.leaving.clear();
+    model.steps = 0;
+}
+
+// Offset in grid units that carries a stone straight out from the grid center to
+// somewhere outside rect. Going the length of the view's diagonal, pan included,
+// leaves it from anywhere on the grid.
+fn offscreen_offset(model: &Model, stone: &Stone, rect: Rect) -> Vec2 {
+    let center = vec2(model.cols as f32 - 1.0, model.rows as f32 - 1.0) / 2.0;
+    let direction = (vec2(stone.x, stone.y) - center).normalize_or_zero();
+    let direction = if direction == Vec2::ZERO { vec2(0.0, 1.0) } else { direction };
+    let distance = (rect.wh().length() + model.pan.length()) / (SIZE as f32 * fit_scale(model, rect));
+    direction * distance
+}
+
+// How much to scale the grid so it and its margin fill the view, whatever the grid
+// size. At the starting size this is 1.
+fn fit_scale(model: &Model, rect: Rect) -> f32 {
+    let w = (model.cols * SIZE + 2 * MARGIN) as f32;
+    let h = (model.rows * SIZE + 2 * MARGIN) as f32;
+    (rect.w() / w).min(rect.h() / h)
 }
 
 fn view(app: &App, model: &Model, frame: Frame) {
     let draw = app.draw();
-    let gdraw = draw.scale(SIZE as f32)
+    let gdraw = draw.xy(-model.pan)
+                    .scale(SIZE as f32 * fit_scale(model, frame.rect()))
                     .scale_y(-1.0)
-                    .x_y(COLS as f32 / -2.0 + 0.5, ROWS as f32 / -2.0 + 0.5);
+                    .x_y(model.cols as f32 / -2.0 + 0.5, model.rows as f32 / -2.0 + 0.5);
 
-    draw.background().color(SNOW);
+    // Without clearing, each frame is drawn over the last and the motion builds up
+    if model.settings.clear_each_frame || model.clearing {
+        draw.background().color(SNOW);
+    }
 
-    for stone in &model.gravel {
+    for stone in model.gravel.iter().chain(&model.leaving) {
         let cdraw = gdraw.x_y(stone.x, stone.y);
+        let size = stone_scale(model, stone);
         cdraw.rect()
             .no_fill()
-            .stroke(BLACK)
+            .stroke(rgba(0.0, 0.0, 0.0, stone_alpha(model, stone)))
             .stroke_weight(LINE_WIDTH)
-            .w_h(1.0, 1.0)
+            .w_h(size, size)
             .x_y(stone.x_offset, stone.y_offset)
             .rotate(stone.rotation)
             ;
     }
 
+    // The clock is left out of captured frames unless asked for
+    if model.recording && (model.clock_in_captures || !model.capturing) {
+        let progress = model.cur_frame as f32 / model.max_frames as f32;
+        draw_clock(&draw, frame.rect().top_right() - vec2(20.0, 20.0), progress);
+    }
+
+    // The paused note is for whoever is at the controls, so it stays out of captures
+    if model.paused && !model.recording && !model.capturing {
+        let rect = frame.rect();
+        draw.text(&format!("Paused at step {}", model.steps))
+            .color(BLACK)
+            .left_justify()
+            .w(200.0)
+            .x_y(rect.left() + 110.0, rect.top() - 15.0)
+            ;
+    }
+
     draw.to_frame(app, &frame).unwrap();
 }
 
+// Opacity of a stone; when fading at rest, moving stones start out bold and
+// dim as they near their target, and resting stones stay faint.
+fn stone_alpha(model: &Model, stone: &Stone) -> f32 {
+    if !model.settings.fade_at_rest {
+        return 1.0;
+    }
+    let moving = stone.x_velocity != 0.0 || stone.y_velocity != 0.0 || stone.rot_velocity != 0.0;
+    if moving {
+        (stone.cycles as f32 / model.settings.cycle_max as f32).clamp(RESTING_ALPHA, 1.0)
+    } else {
+        RESTING_ALPHA
+    }
+}
+
+// Drawn size of a stone. A moving stone shrinks by up to scale_dip halfway to its
+// target and grows back as it settles; resting stones keep their full size.
+fn stone_scale(model: &Model, stone: &Stone) -> f32 {
+    let moving = stone.x_velocity != 0.0 || stone.y_velocity != 0.0 || stone.rot_velocity != 0.0;
+    if model.settings.scale_dip == 0.0 || !moving || stone.total_cycles == 0 {
+        return 1.0;
+    }
+    let t = 1.0 - stone.cycles as f32 / stone.total_cycles as f32;
+    1.0 - model.settings.scale_dip * (PI * t).sin()
+}
+
+// Value following a command line flag, e.g. arg_value("--ui-scale") for "--ui-scale 2"
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).cloned()
+}
+
+// Put the adjustable parameters back to how the program starts, and start the stones over.
+fn reset_defaults(model: &mut Model) {
+    model.pan = vec2(0.0, 0.0);
+    model.settings = Settings::default();
+    for stone in &mut model.gravel {
+        *stone = Stone::new(stone.x, stone.y);
+    }
+}
+
+// Squeeze a point from the square [-half, half] into the ellipse inscribed in it.
+// Aspect is width over height; the longer axis keeps the full half-width.
+fn ellipse_bound(x: f32, y: f32, half: f32, aspect: f32) -> (f32, f32) {
+    if half == 0.0 {
+        return (x, y);
+    }
+    let u = x / half;
+    let v = y / half;
+    let (a, b) = if aspect >= 1.0 {
+        (half, half / aspect)
+    } else {
+        (half * aspect, half)
+    };
+    // Square-to-disc mapping keeps the points spread out instead of piling them on the rim
+    (a * u * (1.0 - v * v / 2.0).sqrt(), b * v * (1.0 - u * u / 2.0).sqrt())
+}
+
+// Pie chart showing recording progress, filling clockwise from the top.
+fn draw_clock(draw: &Draw, center: Point2, progress: f32) {
+    let radius = 12.0;
+    let end = progress.min(1.0) * 2.0 * PI;
+    let points = (0..=60).map(|i| {
+        let angle = end * i as f32 / 60.0;
+        center + vec2(angle.sin(), angle.cos()) * radius
+    });
+    draw.polygon()
+        .color(rgba(1.0, 0.0, 0.0, 0.6))
+        .points(std::iter::once(center).chain(points));
+    draw.ellipse()
+        .no_fill()
+        .stroke(RED)
+        .stroke_weight(1.5)
+        .xy(center)
+        .radius(radius);
+}
+
 fn key_pressed(app: &App, model: &mut Model, key: Key) {
     match key {
-        Key::S => {
-            match app.window(model.main_window) {
-                Some(window) => {
-                    window.capture_frame(app.exe_name().unwrap() + ".png");
-                }
-                None => {}
-            }
+        Key::P => {
+            model.screenshot_requested = true;
         }
         Key::R => {
             if model.recording {
@@ -223,22 +547,68 @@ fn key_pressed(app: &App, model: &mut Model, key: Key) {
                 });
                 model.recording = true;
                 model.cur_frame = 0;
+                if model.lock_seed {
+                    restart_motion(model);
+                }
+                // Pad frame numbers enough for the whole recording so they sort in order
+                model.frame_digits = model.max_frames.to_string().len();
             }
         }
         Key::Up => {
-            model.disp_adj += 0.1;
+            model.settings.disp_adj += 0.1 * step_scale(app);
         }
         Key::Down => {
-            if model.disp_adj > 0.0 {
-                model.disp_adj -= 0.1;
-            }
+            model.settings.disp_adj = (model.settings.disp_adj - 0.1 * step_scale(app)).max(0.0);
         }
         Key::Right => {
-            model.rot_adj += 0.1;
+            model.settings.rot_adj += 0.1 * step_scale(app);
         }
         Key::Left => {
-            if model.rot_adj > 0.0 {
-                model.rot_adj -= 0.1;
+            model.settings.rot_adj = (model.settings.rot_adj - 0.1 * step_scale(app)).max(0.0);
+        }
+        Key::C => {
+            model.clock_in_captures = !model.clock_in_captures;
+        }
+        Key::X => {
+            model.clear_requested = true;
+        }
+        Key::Space => {
+            model.paused = !model.paused;
+        }
+        Key::Return if model.paused => {
+            model.single_step = true;
+        }
+        Key::W => {
+            model.pan.y += PAN_STEP * step_scale(app);
+        }
+        Key::A => {
+            model.pan.x -= PAN_STEP * step_scale(app);
+        }
+        Key::S => {
+            model.pan.y -= PAN_STEP * step_scale(app);
+        }
+        Key::D => {
+            model.pan.x += PAN_STEP * step_scale(app);
+        }
+        Key::Key0 => reset_defaults(model),
+        Key::L => {
+            model.lock_seed = !model.lock_seed;
+            if model.lock_seed {
+                restart_motion(model);
+            }
+            let title = if model.lock_seed {
+                format!("{}  seed {} locked", app.exe_name().unwrap(), model.random_seed)
+            } else {
+                app.exe_name().unwrap()
+            };
+            if let Some(window) = app.window(model.main_window) {
+                window.set_title(&title);
+            }
+        }
+        Key::H => {
+            model.ui_visible = !model.ui_visible;
+            if let Some(window) = app.window(model.ui_window) {
+                window.set_visible(model.ui_visible);
             }
         }
         _other_key => {}
@@ -246,6 +616,17 @@ fn key_pressed(app: &App, model: &mut Model, key: Key) {
 
 }
 
+// Keyboard adjustments are coarser with Shift held and finer with Ctrl held.
+fn step_scale(app: &App) -> f32 {
+    if app.keys.mods.shift() {
+        5.0
+    } else if app.keys.mods.ctrl() {
+        0.1
+    } else {
+        1.0
+    }
+}
+
 fn ui_view(app: &App, model: &Model, frame: Frame) {
     model.ui.draw_to_frame_if_changed(app, &frame).unwrap();
 }
@@ -255,60 +636,192 @@ fn raw_ui_event(app: &App, model: &mut Model, event: &nannou_conrod::RawWindowEv
 }
 
 fn update_ui(model: &mut Model) {
+    let scale = model.ui_scale;
     let ui = &mut model.ui.set_widgets();
 
     // Control panel title
     widget::Text::new("Schotter Control Panel")
-        .top_left_with_margin(10.0)
-        .w_h(300.0, 40.0)
-        .font_size(24)
+        .top_left_with_margin(10.0 * scale)
+        .w_h(300.0 * scale, 40.0 * scale)
+        .font_size((24.0 * scale) as u32)
         .set(model.ids.title, ui);
 
     // Displacement label
     widget::Text::new("Displacement")
-        .down_from(model.ids.title, 15.0)
-        .w_h(125.0, 30.0)
+        .down_from(model.ids.title, 15.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
         .set(model.ids.disp_label, ui);
 
     // Displacement slider
-    for value in widget::Slider::new(model.disp_adj, 0.0, 5.0)
-        .right_from(model.ids.disp_label, 10.0)
-        .w_h(150.0, 30.0)
-        .label(&model.disp_adj.to_string())
+    for value in widget::Slider::new(model.settings.disp_adj, 0.0, 5.0)
+        .right_from(model.ids.disp_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.disp_adj.to_string())
         .set(model.ids.disp_slider, ui)
     {
-        model.disp_adj = value;
+        model.settings.disp_adj = value;
     }
 
     // Rotation label
     widget::Text::new("Rotation")
-        .down_from(model.ids.disp_label, 10.00)
-        .w_h(125.0, 30.0)
+        .down_from(model.ids.disp_label, 10.00 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
         .set(model.ids.rot_label, ui);
 
     // Rotation slider
-    for value in widget::Slider::new(model.rot_adj, 0.0, 5.0)
-        .right_from(model.ids.rot_label, 10.0)
-        .w_h(150.0, 30.0)
-        .label(&model.rot_adj.to_string())
+    for value in widget::Slider::new(model.settings.rot_adj, 0.0, 5.0)
+        .right_from(model.ids.rot_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.rot_adj.to_string())
         .set(model.ids.rot_slider, ui)
     {
-        model.rot_adj = value;
+        model.settings.rot_adj = value;
     }
 
     // Motion label
     widget::Text::new("Motion")
-        .down_from(model.ids.rot_label, 10.0)
-        .w_h(125.0, 30.0)
+        .down_from(model.ids.rot_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
         .set(model.ids.motion_label, ui);
 
     // Motion slider
-    for value in widget::Slider::new(model.motion, 0.0, 1.0)
-        .right_from(model.ids.motion_label, 10.0)
-        .w_h(150.0, 30.0)
-        .label(&model.motion.to_string())
+    for value in widget::Slider::new(model.settings.motion, 0.0, 1.0)
+        .right_from(model.ids.motion_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.motion.to_string())
         .set(model.ids.motion_slider, ui)
     {
-        model.motion = value;
+        model.settings.motion = value;
+    }
+
+    // Cycle minimum label
+    widget::Text::new("Cycle Min")
+        .down_from(model.ids.motion_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.cycle_min_label, ui);
+
+    // Cycle minimum slider
+    if let Some(value) = widget::Slider::new(model.settings.cycle_min as f32, 1.0, 999.0)
+        .right_from(model.ids.cycle_min_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.cycle_min.to_string())
+        .set(model.ids.cycle_min_slider, ui)
+    {
+        model.settings.cycle_min = value as u32;
+    }
+
+    // Cycle maximum label
+    widget::Text::new("Cycle Max")
+        .down_from(model.ids.cycle_min_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.cycle_max_label, ui);
+
+    // Cycle maximum slider
+    if let Some(value) = widget::Slider::new(model.settings.cycle_max as f32, 1.0, 1000.0)
+        .right_from(model.ids.cycle_max_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.cycle_max.to_string())
+        .set(model.ids.cycle_max_slider, ui)
+    {
+        model.settings.cycle_max = value as u32;
+    }
+
+    // random_range() needs a non-empty range; the minimum stops short of the
+    // maximum's top so the guard keeps cycle_max within its slider
+    model.settings.cycle_min = model.settings.cycle_min.min(999);
+    model.settings.cycle_max = model.settings.cycle_max.max(model.settings.cycle_min + 1);
+
+    // Fade resting stones toggle
+    for value in widget::Toggle::new(model.settings.fade_at_rest)
+        .down_from(model.ids.cycle_max_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Fade Resting")
+        .set(model.ids.fade_toggle, ui)
+    {
+        model.settings.fade_at_rest = value;
+    }
+
+    // Elliptical bounds toggle
+    for value in widget::Toggle::new(model.settings.elliptical)
+        .down_from(model.ids.fade_toggle, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Elliptical")
+        .set(model.ids.ellipse_toggle, ui)
+    {
+        model.settings.elliptical = value;
+    }
+
+    // Ellipse aspect label
+    widget::Text::new("Aspect")
+        .down_from(model.ids.ellipse_toggle, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.aspect_label, ui);
+
+    // Ellipse aspect slider
+    if let Some(value) = widget::Slider::new(model.settings.aspect, 0.25, 4.0)
+        .right_from(model.ids.aspect_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&format!("{:.2}", model.settings.aspect))
+        .set(model.ids.aspect_slider, ui)
+    {
+        model.settings.aspect = value;
+    }
+
+    // Rows label
+    widget::Text::new("Rows")
+        .down_from(model.ids.aspect_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.rows_label, ui);
+
+    // Rows slider
+    if let Some(value) = widget::Slider::new(model.settings.target_rows as f32, 1.0, 40.0)
+        .right_from(model.ids.rows_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.target_rows.to_string())
+        .set(model.ids.rows_slider, ui)
+    {
+        model.settings.target_rows = value.round() as u32;
+    }
+
+    // Columns label
+    widget::Text::new("Columns")
+        .down_from(model.ids.rows_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.cols_label, ui);
+
+    // Columns slider
+    if let Some(value) = widget::Slider::new(model.settings.target_cols as f32, 1.0, 30.0)
+        .right_from(model.ids.cols_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&model.settings.target_cols.to_string())
+        .set(model.ids.cols_slider, ui)
+    {
+        model.settings.target_cols = value.round() as u32;
+    }
+
+    // Clear each frame toggle
+    for value in widget::Toggle::new(model.settings.clear_each_frame)
+        .down_from(model.ids.cols_label, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .label("Clear Frames")
+        .set(model.ids.clear_toggle, ui)
+    {
+        model.settings.clear_each_frame = value;
+    }
+
+    // Scale dip label
+    widget::Text::new("Scale dip")
+        .down_from(model.ids.clear_toggle, 10.0 * scale)
+        .w_h(125.0 * scale, 30.0 * scale)
+        .set(model.ids.scale_dip_label, ui);
+
+    // Scale dip slider
+    if let Some(value) = widget::Slider::new(model.settings.scale_dip, 0.0, 0.9)
+        .right_from(model.ids.scale_dip_label, 10.0 * scale)
+        .w_h(150.0 * scale, 30.0 * scale)
+        .label(&format!("{:.2}", model.settings.scale_dip))
+        .set(model.ids.scale_dip_slider, ui)
+    {
+        model.settings.scale_dip = value;
     }
 }
@@ -0,0 +1,58 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use nannou::noise::Fbm;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::SeedableRng;
+
+// Not every field is read by the benchmark
+#[allow(dead_code)]
+#[path = "../src/stones.rs"]
+mod stones;
+use stones::{step_stones, EasingKind, FactorShape, MotionParams, Stone};
+
+const ROWS: u32 = 200;
+const COLS: u32 = 200;
+
+fn params(noise: Option<&Fbm>) -> MotionParams<'_> {
+    MotionParams {
+        rows: ROWS,
+        motion: 0.5,
+        restlessness: 0.0,
+        cycle_min: 50,
+        cycle_max: 300,
+        disp_adj: 1.0,
+        rot_adj: 1.0,
+        rot_bias: 0.0,
+        factor_gamma: 1.0,
+        factor_shape: FactorShape::Linear,
+        peak: 0.5,
+        easing: EasingKind::Linear,
+        max_offset_x: f32::INFINITY,
+        max_offset_y: f32::INFINITY,
+        noise,
+        time: 0.0,
+    }
+}
+
+fn step(c: &mut Criterion) {
+    let mut gravel = Vec::new();
+    for y in 0..ROWS {
+        for x in 0..COLS {
+            gravel.push(Stone::new(x as f32, y as f32));
+        }
+    }
+
+    let mut rng = StdRng::seed_from_u64(42);
+    let random = params(None);
+    c.bench_function("step_stones 200x200", |b| {
+        b.iter(|| step_stones(&mut gravel, &random, &mut rng))
+    });
+
+    let fbm = Fbm::new();
+    let noise = params(Some(&fbm));
+    c.bench_function("step_stones 200x200 noise", |b| {
+        b.iter(|| step_stones(&mut gravel, &noise, &mut rng))
+    });
+}
+
+criterion_group!(benches, step);
+criterion_main!(benches);
@@ -1,7 +1,23 @@
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use nannou::color::Hue;
+use nannou::lyon::tessellation::{LineCap, LineJoin};
+use nannou::noise::{Fbm, MultiFractal, Seedable};
 use nannou::prelude::*;
+use nannou::rand::rngs::StdRng;
+use nannou::rand::{Rng, SeedableRng};
 use nannou_egui::{self, egui, Egui};
+use rand_chacha::ChaCha12Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
 use std::fs;
-use std::io::ErrorKind;
+use std::hash::{Hash, Hasher};
+use std::path::Path;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+mod stones;
+use stones::{step_stones, EasingKind, FactorShape, MotionParams, Stone};
 
 const ROWS: u32 = 22;
 const COLS: u32 = 12;
@@ -10,56 +26,453 @@ const LINE_WIDTH: f32 = 0.06;
 const MARGIN: u32 = 35;
 const WIDTH: u32 = COLS * SIZE + 2 * MARGIN;
 const HEIGHT: u32 = ROWS * SIZE + 2 * MARGIN;
+const PAN_STEP: f32 = 10.0;
+const STATUS_INTERVAL: u64 = 120;
+const TITLE_INTERVAL: u64 = 15;
+const HISTOGRAM_INTERVAL: u64 = 15;
+const HISTOGRAM_BINS: usize = 20;
+const RESTING_ALPHA: f32 = 0.15;
+const SESSION_FILE: &str = "last_session.json";
+const MAX_TRACE_SAMPLES: usize = 5000;
+// Motion steps per second when the motion follows the clock, and the most a slow
+// frame may catch up on
+const STEP_RATE: f64 = 60.0;
+const MAX_STEPS_PER_FRAME: u32 = 10;
+// Steps a stone takes to fly in or out when the grid is resized
+const GRID_CYCLES: u32 = 60;
+
+// Intensity presets for keys 1 to 3: name, displacement, rotation, motion
+const INTENSITY_PRESETS: [(&str, f32, f32, f32); 3] = [
+    ("calm", 0.5, 0.5, 0.2),
+    ("medium", 1.0, 1.0, 0.5),
+    ("chaos", 3.0, 3.0, 1.0),
+];
 
 fn main() {
-    nannou::app(model).update(update).loop_mode(LoopMode::refresh_sync()).run()
-}
-
-struct Stone {
-    x: f32,
-    y: f32,
-    x_offset: f32,
-    y_offset: f32,
-    rotation: f32,
-    x_velocity: f32,
-    y_velocity: f32,
-    rot_velocity: f32,
-    cycles: u32,
-}
-
-impl Stone {
-    fn new(x: f32, y: f32) -> Self {
-        let x_offset = 0.0;
-        let y_offset = 0.0;
-        let rotation = 0.0;
-        let x_velocity = 0.0;
-        let y_velocity = 0.0;
-        let rot_velocity = 0.0;
-        let cycles = 0;
-        Stone {
-            x,
-            y,
-            x_offset,
-            y_offset,
-            rotation,
-            x_velocity,
-            y_velocity,
-            rot_velocity,
-            cycles,
+    nannou::app(model).update(update).exit(exit).loop_mode(LoopMode::refresh_sync()).run()
+}
+
+// Everything needed to pick up where the last run left off.
+#[derive(Serialize, Deserialize)]
+struct Session {
+    disp_adj: f32,
+    rot_adj: f32,
+    motion: f32,
+    // Where the motion is in its random sequence. Older sessions don't have it,
+    // and resuming one of those carries on with the current seed.
+    #[serde(default)]
+    seed: Option<u64>,
+    #[serde(default)]
+    steps: u64,
+    #[serde(default)]
+    rng_position: u128,
+    #[serde(default)]
+    drift_phase: f64,
+    #[serde(default)]
+    noise_seed: Option<u32>,
+    gravel: Vec<Stone>,
+}
+
+#[derive(PartialEq)]
+enum AudioTarget {
+    Motion,
+    Displacement,
+}
+
+#[derive(Clone, Copy)]
+enum Param {
+    Displacement,
+    Rotation,
+    Motion,
+}
+
+const PARAMS: [Param; 3] = [Param::Displacement, Param::Rotation, Param::Motion];
+
+impl Param {
+    fn name(self) -> &'static str {
+        match self {
+            Param::Displacement => "displacement",
+            Param::Rotation => "rotation",
+            Param::Motion => "motion",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Param> {
+        PARAMS.iter().copied().find(|param| param.name() == name)
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Spacing {
+    Uniform,
+    Logarithmic,
+}
+
+impl Spacing {
+    // Positions of n rows or columns in grid units, always spanning 0 to n - 1
+    fn positions(self, n: u32) -> Vec<f32> {
+        if n <= 1 {
+            return vec![0.0; n as usize];
+        }
+        let last = (n - 1) as f32;
+        (0..n).map(|i| match self {
+            Spacing::Uniform => i as f32,
+            Spacing::Logarithmic => last * (1.0 + 9.0 * i as f32 / last).ln() / 10.0f32.ln(),
+        }).collect()
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum CaptureFormat {
+    Png,
+    Jpeg,
+}
+
+impl CaptureFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            CaptureFormat::Png => "png",
+            CaptureFormat::Jpeg => "jpg",
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum ExportAspect {
+    Native,
+    Square,
+    Wide,
+    A4Portrait,
+    A4Landscape,
+}
+
+impl ExportAspect {
+    // Width over height, or None to keep the window's own shape
+    fn ratio(self) -> Option<f32> {
+        match self {
+            ExportAspect::Native => None,
+            ExportAspect::Square => Some(1.0),
+            ExportAspect::Wide => Some(16.0 / 9.0),
+            ExportAspect::A4Portrait => Some(210.0 / 297.0),
+            ExportAspect::A4Landscape => Some(297.0 / 210.0),
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Colormap {
+    Grayscale,
+    Heat,
+    CoolWarm,
+}
+
+impl Colormap {
+    // Map a value from 0.0 to 1.0 onto an RGB color
+    fn color(self, t: f32) -> [u8; 3] {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Colormap::Grayscale => {
+                let v = (t * 255.0) as u8;
+                [v, v, v]
+            }
+            Colormap::Heat => {
+                let r = (3.0 * t).min(1.0);
+                let g = (3.0 * t - 1.0).clamp(0.0, 1.0);
+                let b = (3.0 * t - 2.0).max(0.0);
+                [(r * 255.0) as u8, (g * 255.0) as u8, (b * 255.0) as u8]
+            }
+            // Blue through light gray to red
+            Colormap::CoolWarm => {
+                let (from, to, t) = if t < 0.5 {
+                    ([59.0, 76.0, 192.0], [221.0, 221.0, 221.0], 2.0 * t)
+                } else {
+                    ([221.0, 221.0, 221.0], [180.0, 4.0, 38.0], 2.0 * t - 1.0)
+                };
+                let mix = |i: usize| (from[i] + (to[i] - from[i]) * t) as u8;
+                [mix(0), mix(1), mix(2)]
+            }
+        }
+    }
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum Shape {
+    Square,
+    Line,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum BlendMode {
+    Normal,
+    Additive,
+    Multiply,
+}
+
+impl BlendMode {
+    // How a stone's color combines with what is already drawn beneath it
+    fn component(self) -> wgpu::BlendComponent {
+        match self {
+            BlendMode::Normal => BLEND_NORMAL,
+            BlendMode::Additive => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::SrcAlpha,
+                dst_factor: wgpu::BlendFactor::One,
+                operation: wgpu::BlendOperation::Add,
+            },
+            BlendMode::Multiply => wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::Dst,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
         }
     }
 }
 
+// Something to do to every stone in the lasso selection
+#[derive(Clone, Copy)]
+enum GroupOp {
+    Reset,
+    Lock,
+    Unlock,
+    Nudge(Vec2),
+}
+
+#[derive(PartialEq, Clone, Copy)]
+enum WaveDirection {
+    Row,
+    Column,
+    Diagonal,
+    Radial,
+}
+
+// A recorded sequence of parameter changes, each stamped with the frame
+// (counted from the start of the recording) on which it happened.
+struct Performance {
+    events: Vec<(u64, Param, f32)>,
+    recording: bool,
+    replaying: bool,
+    start_frame: u64,
+    next_event: usize,
+    last_values: [f32; 3],
+}
+
+// An export waiting to be confirmed: the name the files will share and the
+// size of the PNG.
+struct ExportPreview {
+    base: String,
+    size: [u32; 2],
+}
+
 struct Model {
     ui: Egui,
     main_window: WindowId,
+    ui_window: WindowId,
+    ui_visible: bool,
     frames_dir: String,
     cur_frame: u32,
     recording: bool,
+    max_frames: u32,
+    frame_digits: usize,
+    capture_format: CaptureFormat,
+    jpeg_quality: u8,
+    skip_duplicate_frames: bool,
+    motion_blur: bool,
+    blur_samples: u32,
+    previous_offsets: Vec<(Vec2, f32)>,
+    trace: bool,
+    traces: Vec<Vec<Vec2>>,
+    last_frame_hash: Option<u64>,
+    histogram: Vec<u32>,
+    histogram_max: f32,
+    capturing: bool,
+    clock_in_captures: bool,
+    pan: Vec2,
+    paused: bool,
+    fixed_rate: bool,
+    step_time: f64,
+    single_step: bool,
+    steps: u64,
+    seed: u64,
+    seed_text: String,
+    // StdRng's generator, used directly so its position can be saved with a session
+    rng: ChaCha12Rng,
+    settings: Settings,
+    lock_seed: bool,
+    noise_seed: u32,
+    noise_key: NoiseKey,
+    noise: Fbm,
+    drift_phase: f64,
+    audio_stream: Option<cpal::Stream>,
+    audio_level: Arc<AtomicU32>,
+    clipboard: Arc<Mutex<Option<arboard::Clipboard>>>,
+    lasso: Vec<Point2>,
+    selection: Vec<usize>,
+    texture: Option<wgpu::Texture>,
+    texture_path: String,
+    texture_requested: bool,
+    col_positions: Vec<f32>,
+    row_positions: Vec<f32>,
+    resize_window: bool,
+    threshold_in_captures: bool,
+    isolate: Option<u32>,
+    export_density: bool,
+    export_aspect: ExportAspect,
+    export_preview: Option<ExportPreview>,
+    export_confirmed: bool,
+    export_wysiwyg: bool,
+    export_scale: f32,
+    performance: Performance,
+    performance_file: String,
+    verbose: bool,
+    title: String,
+    save_session: bool,
+    stone_shape: Option<Vec<Point2>>,
+    rows: u32,
+    cols: u32,
+    gravel: Vec<Stone>,
+    // Stones dropped by a smaller grid, each with the home it is flying away from
+    leaving: Vec<(Vec2, Stone)>,
+}
+
+// The controls that 0 and the panel's reset button put back to their defaults. model()
+// starts from the same values, so there is only one place to change a default.
+struct Settings {
     disp_adj: f32,
     rot_adj: f32,
+    rot_bias: f32,
+    factor_gamma: f32,
+    factor_shape: FactorShape,
+    peak: f32,
     motion: f32,
-    gravel: Vec<Stone>
+    restlessness: f32,
+    max_offset_x: f32,
+    max_offset_y: f32,
+    cycle_min: u32,
+    cycle_max: u32,
+    easing: EasingKind,
+    separation_strength: f32,
+    noise: bool,
+    noise_octaves: u32,
+    noise_lacunarity: f32,
+    noise_persistence: f32,
+    drift_speed: f32,
+    audio_enabled: bool,
+    audio_target: AudioTarget,
+    audio_sensitivity: f32,
+    show_locks: bool,
+    shape: Shape,
+    line_join: LineJoin,
+    line_cap: LineCap,
+    corner_radius: f32,
+    scale_dip: f32,
+    fill: bool,
+    blend: BlendMode,
+    background: [u8; 3],
+    cell_w: f32,
+    cell_h: f32,
+    target_rows: u32,
+    target_cols: u32,
+    spacing: Spacing,
+    global_alpha: f32,
+    rot_alpha_scale: f32,
+    line_width_jitter: f32,
+    fade_at_rest: bool,
+    shadow: bool,
+    shadow_offset: Vec2,
+    show_anchors: bool,
+    anchor_size: f32,
+    anchor_color: [u8; 3],
+    show_cells: bool,
+    cell_color: [u8; 3],
+    cell_weight: f32,
+    show_threshold: bool,
+    threshold: f32,
+    sort_draw: bool,
+    cap_drawn: bool,
+    max_drawn_stones: usize,
+    wrap: bool,
+    trails: bool,
+    trail_fade: f32,
+    grid_rotation: f32,
+    colormap: Colormap,
+    color_by_displacement: bool,
+    color_range: f32,
+    hue_cycle_speed: f32,
+    wave_amplitude: f32,
+    wave_speed: f32,
+    wave_direction: WaveDirection,
+    smooth_reseed: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            disp_adj: 1.0,
+            rot_adj: 1.0,
+            rot_bias: 0.0,
+            factor_gamma: 1.0,
+            factor_shape: FactorShape::Linear,
+            peak: 0.5,
+            motion: 0.5,
+            restlessness: 0.0,
+            max_offset_x: f32::INFINITY,
+            max_offset_y: f32::INFINITY,
+            cycle_min: 50,
+            cycle_max: 300,
+            easing: EasingKind::Linear,
+            separation_strength: 0.0,
+            noise: false,
+            noise_octaves: 1,
+            noise_lacunarity: 2.0,
+            noise_persistence: 0.5,
+            drift_speed: 0.005,
+            audio_enabled: false,
+            audio_target: AudioTarget::Motion,
+            audio_sensitivity: 10.0,
+            show_locks: true,
+            shape: Shape::Square,
+            line_join: LineJoin::Miter,
+            line_cap: LineCap::Butt,
+            corner_radius: 0.0,
+            scale_dip: 0.0,
+            fill: false,
+            blend: BlendMode::Normal,
+            background: [255, 250, 250],
+            cell_w: SIZE as f32,
+            cell_h: SIZE as f32,
+            target_rows: ROWS,
+            target_cols: COLS,
+            spacing: Spacing::Uniform,
+            global_alpha: 1.0,
+            rot_alpha_scale: 0.0,
+            line_width_jitter: 0.0,
+            fade_at_rest: false,
+            shadow: false,
+            shadow_offset: vec2(3.0, -3.0),
+            show_anchors: false,
+            anchor_size: 0.1,
+            anchor_color: [128, 128, 128],
+            show_cells: false,
+            cell_color: [200, 200, 210],
+            cell_weight: 0.02,
+            show_threshold: false,
+            threshold: 0.1,
+            sort_draw: false,
+            cap_drawn: false,
+            max_drawn_stones: 100,
+            wrap: false,
+            trails: false,
+            trail_fade: 0.1,
+            grid_rotation: 0.0,
+            colormap: Colormap::Grayscale,
+            color_by_displacement: false,
+            color_range: 1.0,
+            hue_cycle_speed: 0.0,
+            wave_amplitude: 0.0,
+            wave_speed: 2.0,
+            wave_direction: WaveDirection::Diagonal,
+            smooth_reseed: true,
+        }
+    }
 }
 
 fn model(app: &App) -> Model {
@@ -68,178 +481,2301 @@ fn model(app: &App) -> Model {
                 .size(WIDTH, HEIGHT)
                 .view(view)
                 .key_pressed(key_pressed)
+                .mouse_pressed(mouse_pressed)
+                .mouse_moved(mouse_moved)
+                .mouse_released(mouse_released)
+                .closed(main_window_closed)
                 .build()
                 .unwrap();
 
+    // Enlarge the controls on high-DPI screens, e.g. --ui-scale 2
+    let ui_scale: f32 = arg_value("--ui-scale").and_then(|scale| scale.parse().ok()).unwrap_or(1.0);
     let ui_window = app.new_window()
                 .title(app.exe_name().unwrap() + " controls")
-                .size(280, 130)
+                .size((280.0 * ui_scale) as u32, (720.0 * ui_scale) as u32)
                 .view(ui_view)
                 .raw_event(raw_ui_event)
                 .key_pressed(key_pressed)
                 .build()
                 .unwrap();
 
+    if std::env::args().any(|arg| arg == "--mirror") {
+        let _mirror_window = app.new_window()
+                    .title(app.exe_name().unwrap() + " mirror")
+                    .size(WIDTH, HEIGHT)
+                    .view(mirror_view)
+                    .key_pressed(key_pressed)
+                    .build()
+                    .unwrap();
+    }
+
     let ui_window_ref = app.window(ui_window).unwrap();
     let ui = Egui::from_window(&ui_window_ref);
+    if ui_scale != 1.0 {
+        scale_egui(ui.ctx(), ui_scale);
+    }
 
-    let frames_dir = app.exe_name().unwrap() + "_frames";
+    let frames_dir = arg_value("--out").unwrap_or(app.exe_name().unwrap() + "_frames");
     let recording = false;
     let cur_frame = 0;
+    // --max-frames 100000 allows longer recordings
+    let max_frames = arg_value("--max-frames").and_then(|frames| frames.parse().ok()).unwrap_or(9999);
+    let frame_digits = 4;
+    let capture_format = CaptureFormat::Png;
+    let jpeg_quality = 90;
+    let skip_duplicate_frames = false;
+    let motion_blur = false;
+    let blur_samples = 2;
+    let previous_offsets = Vec::new();
+    let trace = false;
+    let traces = Vec::new();
+    let last_frame_hash = None;
+    let histogram = vec![0; HISTOGRAM_BINS];
+    let histogram_max = 0.0;
+    let capturing = false;
+    let clock_in_captures = false;
+    let pan = vec2(0.0, 0.0);
+    let paused = false;
+    let fixed_rate = true;
+    let step_time = 0.0;
+    let single_step = false;
+    let steps = 0;
+    let seed = random::<u64>();
+    let seed_text = seed.to_string();
+    let rng = ChaCha12Rng::seed_from_u64(seed);
+    let settings = Settings::default();
+    let lock_seed = false;
+
+    // Kept apart from the motion seed so the noise field can change on its own
+    let noise_seed = random::<u32>();
+    let noise_key = noise_key(noise_seed, &settings);
+    let noise = build_noise(noise_key);
+    let drift_phase = 0.0;
+
+    let audio_level = Arc::new(AtomicU32::new(0.0f32.to_bits()));
+    // The input stream is only opened while audio input is on, so the microphone
+    // isn't held otherwise
+    let audio_stream = None;
+    // X11 only serves the clipboard while it is open, so it lives as long as the model
+    let clipboard = Arc::new(Mutex::new(None));
+
+    let lasso = Vec::new();
+    let selection = Vec::new();
+    // --texture wood.png fills the stones with an image
+    let texture_path = arg_value("--texture").unwrap_or_default();
+    let texture = if texture_path.is_empty() { None } else { load_texture(app, &texture_path) };
+    let texture_requested = false;
+    let col_positions = settings.spacing.positions(COLS);
+    let row_positions = settings.spacing.positions(ROWS);
+    let resize_window = false;
+    let threshold_in_captures = false;
+    let isolate = None;
+    let export_density = false;
+    let export_aspect = ExportAspect::Native;
+    let export_preview = None;
+    let export_confirmed = false;
+    let export_wysiwyg = false;
+    let export_scale = 1.0;
 
-    let disp_adj = 1.0;
-    let rot_adj = 1.0;
-    let motion = 0.5;
+    let performance = Performance {
+        events: Vec::new(),
+        recording: false,
+        replaying: false,
+        start_frame: 0,
+        next_event: 0,
+        last_values: [0.0; 3],
+    };
+    let performance_file = app.exe_name().unwrap() + "_performance.txt";
 
+    let verbose = std::env::args().any(|arg| arg == "--verbose");
+    let stone_shape = arg_value("--shape").and_then(|path| {
+        let shape = load_stone_shape(&path);
+        if shape.is_none() {
+            eprintln!("Could not read a stone shape from {:?}, using squares", path);
+        }
+        shape
+    });
+
+    let rows = ROWS;
+    let cols = COLS;
     let mut gravel = Vec::new();
-    for y in 0..ROWS {
-        for x in 0..COLS {
+    for y in 0..rows {
+        for x in 0..cols {
             let stone = Stone::new(x as f32, y as f32);
             gravel.push(stone);
         }
     }
+    assign_line_jitter(&mut gravel, seed);
+    assign_restlessness(&mut gravel, seed);
+    let leaving = Vec::new();
+
+    let title = app.exe_name().unwrap();
+    let save_session = std::env::args().any(|arg| arg == "--save-session");
 
-    Model {
+    // The controls can be hidden with H and brought back the same way
+    let ui_visible = true;
+
+    let mut model = Model {
         ui,
         main_window,
+        ui_window,
+        ui_visible,
         frames_dir,
         recording,
         cur_frame,
-        disp_adj,
-        rot_adj,
-        motion,
+        max_frames,
+        frame_digits,
+        capture_format,
+        jpeg_quality,
+        skip_duplicate_frames,
+        motion_blur,
+        blur_samples,
+        previous_offsets,
+        trace,
+        traces,
+        last_frame_hash,
+        histogram,
+        histogram_max,
+        capturing,
+        clock_in_captures,
+        pan,
+        paused,
+        fixed_rate,
+        step_time,
+        single_step,
+        steps,
+        seed,
+        seed_text,
+        rng,
+        settings,
+        lock_seed,
+        noise_seed,
+        noise_key,
+        noise,
+        drift_phase,
+        audio_stream,
+        audio_level,
+        clipboard,
+        lasso,
+        selection,
+        texture,
+        texture_path,
+        texture_requested,
+        col_positions,
+        row_positions,
+        resize_window,
+        threshold_in_captures,
+        isolate,
+        export_density,
+        export_aspect,
+        export_preview,
+        export_confirmed,
+        export_wysiwyg,
+        export_scale,
+        performance,
+        performance_file,
+        verbose,
+        title,
+        save_session,
+        stone_shape,
+        rows,
+        cols,
         gravel,
+        leaving,
+    };
+    if std::env::args().any(|arg| arg == "--restore") {
+        resume_session(&mut model, SESSION_FILE);
     }
+    model
 }
 
-fn update(app: &App, model: &mut Model, _update: Update) {
-    update_ui(model);
-    for stone in &mut model.gravel {
-        if stone.cycles == 0 {
-            if random_f32() > model.motion {
-                stone.x_velocity = 0.0;
-                stone.y_velocity = 0.0;
-                stone.rot_velocity = 0.0;
-                stone.cycles = random_range(50, 300);
-            } else {
-                let factor = stone.y / ROWS as f32;
-                let disp_factor = factor * model.disp_adj;
-                let rot_factor = factor * model.rot_adj;
-                let new_x = disp_factor * random_range(-0.5, 0.5);
-                let new_y = disp_factor * random_range(-0.5, 0.5);
-                let new_rot = rot_factor * random_range(-PI / 4.0, PI / 4.0);
-                let new_cycles = random_range(50, 300);
-                stone.x_velocity = (new_x - stone.x_offset) / new_cycles as f32;
-                stone.y_velocity = (new_y - stone.y_offset) / new_cycles as f32;
-                stone.rot_velocity = (new_rot - stone.rotation) / new_cycles as f32;
-                stone.cycles = new_cycles;
-            }
-        } else {
-            stone.x_offset += stone.x_velocity;
-            stone.y_offset += stone.y_velocity;
-            stone.rotation += stone.rot_velocity;
-            stone.cycles -= 1;
+// Value following a command line flag, e.g. arg_value("--shape") for "--shape pebble.svg"
+fn arg_value(name: &str) -> Option<String> {
+    let args: Vec<String> = std::env::args().collect();
+    let index = args.iter().position(|arg| arg == name)?;
+    args.get(index + 1).cloned()
+}
+
+// Load an image for filling the stones, reporting why if it can't be read.
+fn load_texture(app: &App, path: &str) -> Option<wgpu::Texture> {
+    match wgpu::Texture::from_path(app, path) {
+        Ok(texture) => Some(texture),
+        Err(error) => {
+            eprintln!("Could not read a texture from {:?}: {}", path, error);
+            None
+        }
+    }
+}
+
+// Read a stone outline from an SVG file (or a file containing just path data),
+// scaled to fit a unit square centered on the origin. Only the M, L, H, V and Z
+// path commands (absolute or relative) are understood.
+fn load_stone_shape(path: &str) -> Option<Vec<Point2>> {
+    let text = fs::read_to_string(path).ok()?;
+    let data = match text.find(" d=\"") {
+        Some(start) => {
+            let rest = &text[start + 4..];
+            &rest[..rest.find('"')?]
         }
+        None => text.as_str(),
+    };
+    let points = parse_path(data)?;
+    if points.len() < 3 {
+        return None;
     }
 
-    if model.recording && app.elapsed_frames() % 2 == 0 {
-        model.cur_frame += 1;
-        if model.cur_frame > 9999 {
-            model.recording = false;
+    let (min_x, max_x) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.x), hi.max(p.x)));
+    let (min_y, max_y) = points.iter().fold((f32::MAX, f32::MIN), |(lo, hi), p| (lo.min(p.y), hi.max(p.y)));
+    let scale = (max_x - min_x).max(max_y - min_y);
+    if scale <= 0.0 {
+        return None;
+    }
+    let center = pt2(min_x + max_x, min_y + max_y) / 2.0;
+    Some(points.iter().map(|p| (*p - center) / scale).collect())
+}
+
+fn parse_path(data: &str) -> Option<Vec<Point2>> {
+    // Put spaces around commands and before minus signs so everything splits on whitespace
+    let mut spaced = String::new();
+    let mut prev = ' ';
+    for c in data.chars() {
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            spaced.push(' ');
+            spaced.push(c);
+            spaced.push(' ');
+        } else if c == ',' {
+            spaced.push(' ');
+        } else if c == '-' && prev != 'e' && prev != 'E' {
+            spaced.push(' ');
+            spaced.push(c);
         } else {
-            let filename = format!("{}/schotter{:>04}.png",
-                model.frames_dir,
-                model.cur_frame);
-            match app.window(model.main_window) {
-                Some(window) => {
-                    window.capture_frame(filename);
-                }
-                None => {}
+            spaced.push(c);
+        }
+        prev = c;
+    }
+
+    let mut tokens = spaced.split_whitespace().peekable();
+    let mut points: Vec<Point2> = Vec::new();
+    let mut current = pt2(0.0, 0.0);
+    let mut command = ' ';
+    while let Some(token) = tokens.peek() {
+        if token.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            command = token.chars().next()?;
+            tokens.next();
+            if command == 'Z' || command == 'z' {
+                break;
             }
+            continue;
+        }
+        let mut number = || tokens.next()?.parse::<f32>().ok();
+        match command {
+            'M' | 'L' => current = pt2(number()?, number()?),
+            'm' | 'l' => current += vec2(number()?, number()?),
+            'H' => current.x = number()?,
+            'h' => current.x += number()?,
+            'V' => current.y = number()?,
+            'v' => current.y += number()?,
+            _ => return None,
+        }
+        points.push(current);
+        // Extra coordinate pairs after a move are treated as lines
+        if command == 'M' {
+            command = 'L';
+        } else if command == 'm' {
+            command = 'l';
         }
     }
+    Some(points)
 }
 
-fn view(app: &App, model: &Model, frame: Frame) {
-    let draw = app.draw();
-    let gdraw = draw.scale(SIZE as f32)
-                    .scale_y(-1.0)
-                    .x_y(COLS as f32 / -2.0 + 0.5, ROWS as f32 / -2.0 + 0.5);
+fn update(app: &App, model: &mut Model, update: Update) {
+    update_ui(model);
+    if (model.settings.target_rows, model.settings.target_cols) != (model.rows, model.cols) {
+        resize_grid(app, model);
+    }
+    if model.texture_requested {
+        model.texture_requested = false;
+        model.texture = load_texture(app, &model.texture_path);
+    }
+    if model.export_confirmed {
+        model.export_confirmed = false;
+        if let Some(preview) = model.export_preview.take() {
+            export_all(app, model, &preview.base);
+        }
+    }
+    if model.resize_window {
+        if let Some(window) = app.window(model.main_window) {
+            window.set_inner_size_points(
+                model.cols as f32 * model.settings.cell_w + 2.0 * MARGIN as f32,
+                model.rows as f32 * model.settings.cell_h + 2.0 * MARGIN as f32,
+            );
+        }
+        model.resize_window = false;
+    }
+    if model.settings.audio_enabled && model.audio_stream.is_none() {
+        model.audio_stream = audio_input(model.audio_level.clone());
+        if model.audio_stream.is_none() {
+            eprintln!("Could not open an audio input device");
+            model.settings.audio_enabled = false;
+        }
+    } else if !model.settings.audio_enabled && model.audio_stream.is_some() {
+        model.audio_stream = None;
+        model.audio_level.store(0.0f32.to_bits(), Ordering::Relaxed);
+    }
+    if model.settings.audio_enabled {
+        let level = f32::from_bits(model.audio_level.load(Ordering::Relaxed)) * model.settings.audio_sensitivity;
+        match model.settings.audio_target {
+            AudioTarget::Motion => model.settings.motion = level.min(1.0),
+            AudioTarget::Displacement => model.settings.disp_adj = level.min(5.0),
+        }
+    }
+    update_performance(app, model);
+    // With fixed_rate the steps follow the clock, so the motion looks the same at 30 or
+    // 144 fps. Recording takes one step per frame so recorded frames are evenly spaced.
+    let steps = if model.paused {
+        model.single_step as u32
+    } else if model.fixed_rate && !model.recording {
+        model.step_time += update.since_last.as_secs_f64();
+        let due = (model.step_time * STEP_RATE).floor();
+        model.step_time -= due / STEP_RATE;
+        (due as u32).min(MAX_STEPS_PER_FRAME)
+    } else {
+        1
+    };
+    // Where each stone was before this frame's steps, for blurring recorded frames
+    if steps > 0 && model.motion_blur && model.recording {
+        model.previous_offsets = model.gravel.iter()
+            .map(|stone| (vec2(stone.x_offset, stone.y_offset), stone.rotation))
+            .collect();
+    }
+    for _ in 0..steps {
+        move_stones(model);
+        model.drift_phase += model.settings.drift_speed as f64;
+        model.steps += 1;
+        if model.trace {
+            record_traces(model);
+        }
+    }
+    model.single_step = false;
 
-    draw.background().color(SNOW);
+    if model.export_density {
+        save_density_map(model, &(app.exe_name().unwrap() + "_density.png"));
+        model.export_density = false;
+    }
 
-    for stone in &model.gravel {
-        let cdraw = gdraw.x_y(stone.x, stone.y);
-        cdraw.rect()
-            .no_fill()
-            .stroke(BLACK)
-            .stroke_weight(LINE_WIDTH)
-            .w_h(1.0, 1.0)
-            .x_y(stone.x_offset, stone.y_offset)
-            .rotate(stone.rotation)
-            ;
+    if model.verbose && app.elapsed_frames() % STATUS_INTERVAL == 0 {
+        println!("displacement {:.2}  rotation {:.2}  motion {:.2}  seed {}{}  fps {:.1}  {}",
+            model.settings.disp_adj,
+            model.settings.rot_adj,
+            model.settings.motion,
+            model.seed,
+            if model.lock_seed { " locked" } else { "" },
+            app.fps(),
+            if model.recording { format!("recording frame {}", model.cur_frame) } else { "not recording".to_string() });
     }
 
-    draw.to_frame(app, &frame).unwrap();
-}
+    // Binning every stone each frame would be wasted on a panel plot
+    if app.elapsed_frames() % HISTOGRAM_INTERVAL == 0 {
+        update_histogram(model);
+    }
 
-fn key_pressed(app: &App, model: &mut Model, key: Key) {
-    match key {
-        Key::S => {
-            match app.window(model.main_window) {
-                Some(window) => {
-                    window.capture_frame(app.exe_name().unwrap() + ".png");
-                }
-                None => {}
+    // Only touch the title now and then, and only when the rounded values change
+    if app.elapsed_frames() % TITLE_INTERVAL == 0 {
+        let mut title = format!("{}  seed {}  d {:.1}  r {:.1}  m {:.1}",
+            app.exe_name().unwrap(),
+            model.seed,
+            model.settings.disp_adj,
+            model.settings.rot_adj,
+            model.settings.motion);
+        if model.lock_seed {
+            title += "  locked";
+        }
+        if model.settings.grid_rotation != 0.0 {
+            title += &format!("  grid {:.0}°", model.settings.grid_rotation);
+        }
+        if title != model.title {
+            if let Some(window) = app.window(model.main_window) {
+                window.set_title(&title);
             }
+            model.title = title;
         }
-        Key::R => {
-            if model.recording {
+    }
+
+    model.capturing = false;
+    if model.recording && app.elapsed_frames() % 2 == 0 {
+        let hash = frame_hash(app, model);
+        let duplicate = model.skip_duplicate_frames && model.last_frame_hash == Some(hash);
+        model.last_frame_hash = Some(hash);
+        if !duplicate {
+            model.cur_frame += 1;
+            if model.cur_frame > model.max_frames {
                 model.recording = false;
             } else {
-                fs::create_dir(&model.frames_dir).unwrap_or_else(|error| {
-                    if error.kind() != ErrorKind::AlreadyExists {
-                        panic!{"Problem creating directory {:?}", model.frames_dir};
+                let filename = format!("{}/schotter{:0width$}.{}",
+                    model.frames_dir,
+                    model.cur_frame,
+                    model.capture_format.extension(),
+                    width = model.frame_digits);
+                match model.capture_format {
+                    CaptureFormat::Png => {
+                        if let Some(window) = app.window(model.main_window) {
+                            window.capture_frame(filename);
+                            model.capturing = true;
+                        }
                     }
-                });
-                model.recording = true;
-                model.cur_frame = 0;
+                    // Window captures are always PNG, so render the frame ourselves
+                    CaptureFormat::Jpeg => {
+                        if let Some(size) = view_size(app, model) {
+                            let quality = model.jpeg_quality;
+                            render_offscreen(app, model, size, true, move |image| {
+                                if let Err(error) = save_jpeg(&image, &filename, quality) {
+                                    eprintln!("Problem writing {:?}: {}", filename, error);
+                                }
+                            });
+                        }
+                    }
+                }
             }
         }
-        Key::Up => {
-            model.disp_adj += 0.1;
+    }
+}
+
+// Cheap fingerprint of where every stone is drawn, to spot frames that
+// would come out the same as the last one.
+fn frame_hash(app: &App, model: &Model) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for stone in &model.gravel {
+        let rotation = stone.rotation + wave_rotation(model, stone, app.time);
+        stone.x_offset.to_bits().hash(&mut hasher);
+        stone.y_offset.to_bits().hash(&mut hasher);
+        rotation.to_bits().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+// Save an image with one block of pixels per stone, colored by how far the
+// stone is from its grid position relative to the farthest stone.
+fn save_density_map(model: &Model, filename: &str) {
+    const CELL: u32 = 10;
+    let magnitudes: Vec<f32> = model.gravel.iter()
+        .map(|stone| vec2(stone.x_offset, stone.y_offset).length())
+        .collect();
+    let max = magnitudes.iter().cloned().fold(0.0, f32::max);
+    let image = nannou::image::RgbImage::from_fn(model.cols * CELL, model.rows * CELL, |x, y| {
+        let magnitude = magnitudes[((y / CELL) * model.cols + x / CELL) as usize];
+        let t = if max > 0.0 { magnitude / max } else { 0.0 };
+        nannou::image::Rgb(model.settings.colormap.color(t))
+    });
+    if let Err(error) = image.save(filename) {
+        eprintln!("Problem writing {:?}: {}", filename, error);
+    }
+}
+
+fn param_value(model: &Model, param: Param) -> f32 {
+    match param {
+        Param::Displacement => model.settings.disp_adj,
+        Param::Rotation => model.settings.rot_adj,
+        Param::Motion => model.settings.motion,
+    }
+}
+
+fn set_param(model: &mut Model, param: Param, value: f32) {
+    match param {
+        Param::Displacement => model.settings.disp_adj = value,
+        Param::Rotation => model.settings.rot_adj = value,
+        Param::Motion => model.settings.motion = value,
+    }
+}
+
+// Record parameter changes, or apply recorded changes when their frame comes up.
+fn update_performance(app: &App, model: &mut Model) {
+    let frame = app.elapsed_frames() - model.performance.start_frame;
+    if model.performance.recording {
+        for (i, param) in PARAMS.iter().enumerate() {
+            let value = param_value(model, *param);
+            if value != model.performance.last_values[i] {
+                model.performance.events.push((frame, *param, value));
+                model.performance.last_values[i] = value;
+            }
         }
-        Key::Down => {
-            if model.disp_adj > 0.0 {
-                model.disp_adj -= 0.1;
+    } else if model.performance.replaying {
+        while let Some(&(event_frame, param, value)) = model.performance.events.get(model.performance.next_event) {
+            if event_frame > frame {
+                break;
             }
+            set_param(model, param, value);
+            model.performance.next_event += 1;
         }
-        Key::Right => {
-            model.rot_adj += 0.1;
+        if model.performance.next_event >= model.performance.events.len() {
+            model.performance.replaying = false;
         }
-        Key::Left => {
-            if model.rot_adj > 0.0 {
-                model.rot_adj -= 0.1;
+    }
+}
+
+fn save_performance(performance: &Performance, filename: &str) {
+    let mut text = String::new();
+    for (frame, param, value) in &performance.events {
+        text += &format!("{} {} {}\n", frame, param.name(), value);
+    }
+    if let Err(error) = fs::write(filename, text) {
+        eprintln!("Problem writing {:?}: {}", filename, error);
+    }
+}
+
+// Stop any recording so the last frames reach the disk, then save the arrangement.
+fn exit(app: &App, model: Model) {
+    if model.recording {
+        if let Some(window) = app.window(model.main_window) {
+            if let Err(error) = window.await_capture_frame_jobs() {
+                eprintln!("Problem finishing recording: {:?}", error);
             }
         }
-        _other_key => {}
+        println!("Recording stopped at frame {}", model.cur_frame);
+    }
+    if model.save_session {
+        save_session(&model, SESSION_FILE);
     }
+}
 
+// Closing the main window ends the program, so the controls window doesn't linger.
+fn main_window_closed(app: &App, _model: &mut Model) {
+    app.quit();
 }
 
-fn ui_view(_app: &App, model: &Model, frame: Frame) {
-    model.ui.draw_to_frame(&frame).unwrap();
+fn save_session(model: &Model, filename: &str) {
+    let session = Session {
+        disp_adj: model.settings.disp_adj,
+        rot_adj: model.settings.rot_adj,
+        motion: model.settings.motion,
+        seed: Some(model.seed),
+        steps: model.steps,
+        rng_position: model.rng.get_word_pos(),
+        drift_phase: model.drift_phase,
+        noise_seed: Some(model.noise_seed),
+        gravel: model.gravel.clone(),
+    };
+    match serde_json::to_string(&session) {
+        Ok(text) => {
+            if let Err(error) = fs::write(filename, text) {
+                eprintln!("Problem writing {:?}: {}", filename, error);
+            }
+        }
+        Err(error) => eprintln!("Problem saving session: {}", error),
+    }
 }
 
-fn raw_ui_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
-    model.ui.handle_raw_event(event);
+fn load_session(filename: &str) -> Option<Session> {
+    let text = fs::read_to_string(filename).ok()?;
+    serde_json::from_str(&text).ok()
 }
 
-fn update_ui(model: &mut Model) {
-    let ctx = model.ui.begin_frame();
-    egui::Window::new("Schotter Control Panel").collapsible(false).show(&ctx, |ui| {
-        ui.add(egui::Slider::new(&mut model.disp_adj, 0.0..=5.0).text("Displacement"));
-        ui.add(egui::Slider::new(&mut model.rot_adj, 0.0..=5.0).text("Rotation"));
-        ui.add(egui::Slider::new(&mut model.motion, 0.0..=1.0).text("Motion"));
+// The newest JSON written by export_all(), or the last session if there is none.
+fn latest_checkpoint(app: &App) -> String {
+    let prefix = app.exe_name().unwrap() + "_";
+    let newest = fs::read_dir(".").ok().and_then(|entries| {
+        entries.flatten()
+            .filter_map(|entry| entry.file_name().into_string().ok())
+            .filter_map(|name| {
+                let stamp: u64 = name.strip_prefix(&prefix)?.strip_suffix(".json")?.parse().ok()?;
+                Some((stamp, name))
+            })
+            .max()
+    });
+    newest.map_or(SESSION_FILE.to_string(), |(_, name)| name)
+}
+
+// Pick up a saved arrangement, velocities and cycles included, and carry on from it.
+// Stones are put back by grid position, so a save from a different grid size keeps
+// the stones that fit and starts the rest fresh.
+fn resume_session(model: &mut Model, filename: &str) {
+    let session = match load_session(filename) {
+        Some(session) => session,
+        None => {
+            eprintln!("Could not resume from {:?}", filename);
+            return;
+        }
+    };
+    let mut gravel: Vec<Stone> = (0..model.rows)
+        .flat_map(|y| (0..model.cols).map(move |x| Stone::new(x as f32, y as f32)))
+        .collect();
+    for stone in session.gravel {
+        let (x, y) = (stone.x as u32, stone.y as u32);
+        if x < model.cols && y < model.rows {
+            gravel[(y * model.cols + x) as usize] = stone;
+        }
+    }
+    model.gravel = gravel;
+    model.leaving.clear();
+    model.settings.disp_adj = session.disp_adj;
+    model.settings.rot_adj = session.rot_adj;
+    model.settings.motion = session.motion;
+    if let Some(seed) = session.seed {
+        model.seed = seed;
+        model.seed_text = seed.to_string();
+        model.steps = session.steps;
+        model.rng = ChaCha12Rng::seed_from_u64(seed);
+        model.rng.set_word_pos(session.rng_position);
+        model.drift_phase = session.drift_phase;
+    }
+    if let Some(noise_seed) = session.noise_seed {
+        model.noise_seed = noise_seed;
+    }
+    model.selection.clear();
+    model.previous_offsets.clear();
+    println!("Resumed from {:?}", filename);
+}
+
+// Render the gravel offscreen at the export size, with every stone whatever the cap
+// on drawn stones and none of the overlays. Unless exporting what's on screen, that
+// is the whole composition whatever the pan.
+fn save_export(app: &App, model: &Model, filename: String) {
+    let size = export_size(app, model);
+    render_offscreen(app, model, size, model.export_wysiwyg, move |image| {
+        if let Err(error) = image.save(&filename) {
+            eprintln!("Problem writing {:?}: {}", filename, error);
+        }
     });
 }
+
+// Put the current view on the clipboard, or in a temporary file if the
+// clipboard can't take images on this platform.
+fn copy_to_clipboard(app: &App, model: &Model) {
+    let size = match view_size(app, model) {
+        Some(size) => size,
+        None => return,
+    };
+    let filename = std::env::temp_dir().join(app.exe_name().unwrap() + ".png");
+    let clipboard = model.clipboard.clone();
+    render_offscreen(app, model, size, true, move |image| {
+        let data = arboard::ImageData {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            bytes: image.as_raw().into(),
+        };
+        let mut clipboard = clipboard.lock().unwrap();
+        let copied = clipboard.take().map_or_else(arboard::Clipboard::new, Ok).and_then(|mut opened| {
+            let result = opened.set_image(data);
+            *clipboard = Some(opened);
+            result
+        });
+        if let Err(error) = copied {
+            match image.save(&filename) {
+                Ok(()) => eprintln!("Could not copy to the clipboard ({}), saved {:?} instead", error, filename),
+                Err(save_error) => eprintln!("Problem writing {:?}: {}", filename, save_error),
+            }
+        }
+    });
+}
+
+// Size of the main window's view in points
+fn view_size(app: &App, model: &Model) -> Option<[u32; 2]> {
+    let (w, h) = app.window(model.main_window)?.rect().w_h();
+    Some([w as u32, h as u32])
+}
+
+fn save_jpeg(image: &nannou::image::RgbaImage, filename: &str, quality: u8) -> nannou::image::ImageResult<()> {
+    let mut file = std::io::BufWriter::new(fs::File::create(filename)?);
+    nannou::image::codecs::jpeg::JpegEncoder::new_with_quality(&mut file, quality).encode_image(image)
+}
+
+// Draw the gravel centered on a texture of the given size and hand the pixels to `done`.
+// With follow_view the pan applies as on screen; without it the grid is centered.
+fn render_offscreen<F>(app: &App, model: &Model, size: [u32; 2], follow_view: bool, done: F)
+where
+    F: 'static + Send + FnOnce(nannou::image::RgbaImage),
+{
+    let window = match app.window(model.main_window) {
+        Some(window) => window,
+        None => return,
+    };
+    let device = window.device();
+    let texture = wgpu::TextureBuilder::new()
+        .size(size)
+        .usage(wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING)
+        .sample_count(1)
+        .format(wgpu::TextureFormat::Rgba16Float)
+        .build(device);
+
+    let draw = Draw::new();
+    let [r, g, b] = model.settings.background;
+    draw.background().color(rgb8(r, g, b));
+    // grid_transform() applies the pan, so shift back by it to leave the view out
+    let camera = if follow_view { draw.clone() } else { draw.xy(model.pan) };
+    draw_gravel(app, model, &camera, Rect::from_w_h(size[0] as f32, size[1] as f32), usize::MAX, false);
+
+    let mut renderer = nannou::draw::RendererBuilder::new()
+        .build_from_texture_descriptor(device, texture.descriptor());
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("offscreen"),
+    });
+    renderer.render_to_texture(device, &mut encoder, &draw, &texture);
+    let capturer = wgpu::TextureCapturer::default();
+    let snapshot = capturer.capture(device, &mut encoder, &texture);
+    window.queue().submit(Some(encoder.finish()));
+
+    // Dropping the capturer waits for the callback to finish
+    let result = snapshot.read(move |result| match result {
+        Ok(image) => done(image.to_owned()),
+        Err(error) => eprintln!("Problem reading the rendered image: {:?}", error),
+    });
+    if let Err(error) = result {
+        eprintln!("Problem rendering offscreen: {}", error);
+    }
+}
+
+// Read a performance saved by save_performance(), skipping any lines that don't parse.
+fn load_performance(filename: &str) -> Option<Vec<(u64, Param, f32)>> {
+    let text = fs::read_to_string(filename).ok()?;
+    let mut events = Vec::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if let [frame, name, value] = fields[..] {
+            if let (Ok(frame), Some(param), Ok(value)) = (frame.parse(), Param::from_name(name), value.parse()) {
+                events.push((frame, param, value));
+            }
+        }
+    }
+    Some(events)
+}
+
+// Open the default input device and keep a smoothed RMS level of its samples in `level`.
+// Returns None if there is no usable input device.
+fn audio_input(level: Arc<AtomicU32>) -> Option<cpal::Stream> {
+    let host = cpal::default_host();
+    let device = host.default_input_device()?;
+    let config = device.default_input_config().ok()?;
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => input_stream::<f32>(&device, &config.into(), level),
+        cpal::SampleFormat::I16 => input_stream::<i16>(&device, &config.into(), level),
+        cpal::SampleFormat::U16 => input_stream::<u16>(&device, &config.into(), level),
+    }?;
+    stream.play().ok()?;
+    Some(stream)
+}
+
+fn input_stream<T: cpal::Sample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    level: Arc<AtomicU32>,
+) -> Option<cpal::Stream> {
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            let sum: f32 = data.iter().map(|sample| sample.to_f32().powi(2)).sum();
+            let rms = (sum / data.len().max(1) as f32).sqrt();
+            let smoothed = f32::from_bits(level.load(Ordering::Relaxed)) * 0.9 + rms * 0.1;
+            level.store(smoothed.to_bits(), Ordering::Relaxed);
+        },
+        |error| eprintln!("Audio input error: {}", error),
+    ).ok()
+}
+
+// Push overlapping stones apart. Stones are treated as circles one cell wide,
+// so this only checks pairs that are close enough to touch. Locked stones stay
+// put, and a stone touching one takes the whole push itself.
+fn separate(gravel: &mut [Stone], strength: f32) {
+    for i in 0..gravel.len() {
+        for j in (i + 1)..gravel.len() {
+            let (locked_i, locked_j) = (gravel[i].locked, gravel[j].locked);
+            if locked_i && locked_j {
+                continue;
+            }
+            let dx = (gravel[j].x + gravel[j].x_offset) - (gravel[i].x + gravel[i].x_offset);
+            let dy = (gravel[j].y + gravel[j].y_offset) - (gravel[i].y + gravel[i].y_offset);
+            let dist = (dx * dx + dy * dy).sqrt();
+            if dist < 1.0 && dist > 0.0 {
+                let push = (1.0 - dist) * strength / dist;
+                let share_i = if locked_i { 0.0 } else if locked_j { 1.0 } else { 0.5 };
+                gravel[i].x_offset -= dx * push * share_i;
+                gravel[i].y_offset -= dy * push * share_i;
+                gravel[j].x_offset += dx * push * (1.0 - share_i);
+                gravel[j].y_offset += dy * push * (1.0 - share_i);
+            }
+        }
+    }
+}
+
+fn move_stones(model: &mut Model) {
+    // The noise field is only rebuilt when its seed or parameters change
+    let key = noise_key(model.noise_seed, &model.settings);
+    if key != model.noise_key {
+        model.noise = build_noise(key);
+        model.noise_key = key;
+    }
+    let noise = model.settings.noise.then_some(&model.noise);
+    let params = motion_params(&model.settings, model.rows, noise, model.drift_phase);
+    step_stones(&mut model.gravel, &params, &mut model.rng);
+
+    if model.settings.separation_strength > 0.0 {
+        separate(&mut model.gravel, model.settings.separation_strength);
+    }
+
+    // Stones dropped from the grid drift away and are forgotten once out of sight
+    for (_, stone) in &mut model.leaving {
+        stone.x_offset += stone.x_velocity;
+        stone.y_offset += stone.y_velocity;
+        stone.cycles -= 1;
+    }
+    model.leaving.retain(|(_, stone)| stone.cycles > 0);
+}
+
+// Rebuild the gravel for the target grid size. Stones still inside the grid are
+// kept; new ones fly in from beyond the window and removed ones fly out.
+fn resize_grid(app: &App, model: &mut Model) {
+    let (old_rows, old_cols) = (model.rows, model.cols);
+    let (rows, cols) = (model.settings.target_rows, model.settings.target_cols);
+    let old_homes: Vec<Vec2> = model.gravel.iter()
+        .map(|stone| vec2(model.col_positions[stone.x as usize], model.row_positions[stone.y as usize]))
+        .collect();
+    let mut old: Vec<Option<Stone>> = std::mem::take(&mut model.gravel).into_iter().map(Some).collect();
+    model.rows = rows;
+    model.cols = cols;
+    model.col_positions = model.settings.spacing.positions(cols);
+    model.row_positions = model.settings.spacing.positions(rows);
+    let rect = app.window(model.main_window).map_or(Rect::from_w_h(WIDTH as f32, HEIGHT as f32), |window| window.rect());
+
+    // New stones get the same line width jitter and restlessness a reseed would give them
+    let mut fresh: Vec<Stone> = (0..rows)
+        .flat_map(|y| (0..cols).map(move |x| Stone::new(x as f32, y as f32)))
+        .collect();
+    assign_line_jitter(&mut fresh, model.seed);
+    assign_restlessness(&mut fresh, model.seed);
+    for mut stone in fresh {
+        let (x, y) = (stone.x as u32, stone.y as u32);
+        let stone = if x < old_cols && y < old_rows {
+            old[(y * old_cols + x) as usize].take().unwrap()
+        } else {
+            let home = vec2(model.col_positions[x as usize], model.row_positions[y as usize]);
+            let start = offscreen_offset(model, home, rect);
+            stone.x_offset = start.x;
+            stone.y_offset = start.y;
+            stone.x_velocity = -start.x / GRID_CYCLES as f32;
+            stone.y_velocity = -start.y / GRID_CYCLES as f32;
+            stone.cycles = GRID_CYCLES;
+            stone.total_cycles = GRID_CYCLES;
+            stone
+        };
+        model.gravel.push(stone);
+    }
+
+    for (home, stone) in old_homes.into_iter().zip(old) {
+        if let Some(mut stone) = stone {
+            let away = offscreen_offset(model, home, rect);
+            stone.x_velocity = away.x / GRID_CYCLES as f32;
+            stone.y_velocity = away.y / GRID_CYCLES as f32;
+            stone.rot_velocity = 0.0;
+            stone.cycles = GRID_CYCLES;
+            stone.total_cycles = GRID_CYCLES;
+            model.leaving.push((home, stone));
+        }
+    }
+
+    // Anything kept by stone index no longer lines up with the gravel
+    model.selection.clear();
+    model.traces.clear();
+    model.previous_offsets.clear();
+    model.isolate = model.isolate.map(|row| row.min(rows - 1));
+}
+
+// Offset in grid units that carries a stone at home straight out from the grid
+// center to somewhere outside rect. Going the length of the view's diagonal, pan
+// included, leaves it whatever the grid's rotation.
+fn offscreen_offset(model: &Model, home: Vec2, rect: Rect) -> Vec2 {
+    let center = vec2(model.cols as f32 - 1.0, model.rows as f32 - 1.0) / 2.0;
+    let direction = (home - center).normalize_or_zero();
+    let direction = if direction == Vec2::ZERO { vec2(0.0, 1.0) } else { direction };
+    let cell = model.settings.cell_w.min(model.settings.cell_h) * fit_scale(model, rect);
+    direction * (rect.wh().length() + model.pan.length()) / cell
+}
+
+// The seed, octaves, lacunarity and persistence a noise field is built from
+type NoiseKey = (u32, u32, f32, f32);
+
+fn noise_key(seed: u32, settings: &Settings) -> NoiseKey {
+    (seed, settings.noise_octaves, settings.noise_lacunarity, settings.noise_persistence)
+}
+
+fn build_noise((seed, octaves, lacunarity, persistence): NoiseKey) -> Fbm {
+    Fbm::new()
+        .set_octaves(octaves as usize)
+        .set_lacunarity(lacunarity as f64)
+        .set_persistence(persistence as f64)
+        .set_seed(seed)
+}
+
+fn motion_params<'a>(settings: &Settings, rows: u32, noise: Option<&'a Fbm>, drift_phase: f64) -> MotionParams<'a> {
+    MotionParams {
+        rows,
+        motion: settings.motion,
+        restlessness: settings.restlessness,
+        cycle_min: settings.cycle_min,
+        cycle_max: settings.cycle_max,
+        disp_adj: settings.disp_adj,
+        rot_adj: settings.rot_adj,
+        rot_bias: settings.rot_bias,
+        factor_gamma: settings.factor_gamma,
+        factor_shape: settings.factor_shape,
+        peak: settings.peak,
+        easing: settings.easing,
+        max_offset_x: settings.max_offset_x,
+        max_offset_y: settings.max_offset_y,
+        noise,
+        time: drift_phase,
+    }
+}
+
+fn view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    draw_gravel(app, model, &draw, frame.rect(), drawn_limit(model), true);
+
+    // Marks where order turns to chaos; like the clock it stays out of captures unless asked for
+    if model.settings.show_threshold && (model.threshold_in_captures || !model.capturing) {
+        if let Some(y) = threshold_y(model) {
+            grid_transform(model, &draw, frame.rect(), vec2(0.0, 0.0))
+                .line()
+                .start(pt2(-0.5, y))
+                .end(pt2(model.cols as f32 - 0.5, y))
+                .weight(LINE_WIDTH / 2.0)
+                .color(rgba(0.8, 0.1, 0.1, 0.8));
+        }
+    }
+
+    // The selection and the lasso being drawn are only there to edit with, so never captured
+    if !model.capturing {
+        let fit = fit_scale(model, frame.rect());
+        let size = model.settings.cell_w.min(model.settings.cell_h) * fit * 0.8;
+        for &index in &model.selection {
+            draw.ellipse()
+                .no_fill()
+                .stroke(rgba(0.1, 0.4, 0.9, 0.8))
+                .stroke_weight(1.5)
+                .w_h(size, size)
+                .xy(stone_window_position(model, &model.gravel[index], fit));
+        }
+        if model.lasso.len() > 1 {
+            draw.polyline()
+                .weight(1.0)
+                .color(rgba(0.1, 0.4, 0.9, 0.8))
+                .points(model.lasso.iter().chain(model.lasso.first()).copied());
+        }
+    }
+
+    // The clock is left out of captured frames unless asked for
+    if model.recording && (model.clock_in_captures || !model.capturing) {
+        let progress = model.cur_frame as f32 / model.max_frames as f32;
+        draw_clock(&draw, frame.rect().top_right() - vec2(20.0, 20.0), progress);
+    }
+
+    let total = model.gravel.len();
+    let drawn = total.div_ceil(drawn_stride(total, drawn_limit(model)));
+    if drawn < total {
+        let rect = frame.rect();
+        draw.text(&format!("Drawing {} of {} stones", drawn, total))
+            .color(BLACK)
+            .left_justify()
+            .w(200.0)
+            .x_y(rect.left() + 110.0, rect.bottom() + 15.0)
+            ;
+    }
+
+    // The paused note is for whoever is at the controls, so it stays out of captures
+    if model.paused && !model.recording && !model.capturing {
+        let rect = frame.rect();
+        draw.text(&format!("Paused at step {}", model.steps))
+            .color(BLACK)
+            .left_justify()
+            .w(200.0)
+            .x_y(rect.left() + 110.0, rect.top() - 15.0)
+            ;
+    }
+
+    draw.to_frame(app, &frame).unwrap();
+}
+
+// The mirror window shows the same gravel flipped left to right.
+fn mirror_view(app: &App, model: &Model, frame: Frame) {
+    let draw = app.draw();
+    draw_gravel(app, model, &draw.scale_x(-1.0), frame.rect(), drawn_limit(model), true);
+    draw.to_frame(app, &frame).unwrap();
+}
+
+// Draw in grid units, one per cell, with the grid centered in rect. The offset is
+// in window pixels.
+fn grid_transform(model: &Model, draw: &Draw, rect: Rect, offset: Vec2) -> Draw {
+    draw.xy(offset - model.pan)
+        .scale(fit_scale(model, rect))
+        .rotate(deg_to_rad(model.settings.grid_rotation))
+        .scale_x(model.settings.cell_w)
+        .scale_y(-model.settings.cell_h)
+        .x_y(model.cols as f32 / -2.0 + 0.5, model.rows as f32 / -2.0 + 0.5)
+}
+
+// Grid y of the boundary above the first row whose stones can move at least
+// `threshold` cells from home, following the factor used in step_stones.
+fn threshold_y(model: &Model) -> Option<f32> {
+    if model.settings.disp_adj <= 0.0 {
+        return None;
+    }
+    let factor = (2.0 * model.settings.threshold / model.settings.disp_adj).powf(1.0 / model.settings.factor_gamma);
+    // The first row to reach the factor is on the rising side of a peak
+    let t = match model.settings.factor_shape {
+        FactorShape::Linear => factor,
+        FactorShape::Peak => factor * model.settings.peak,
+    };
+    let row = (model.rows as f32 * t).ceil() as usize;
+    let positions = &model.row_positions;
+    match row {
+        0 => Some(positions[0] - 0.5),
+        row if row < positions.len() => Some((positions[row - 1] + positions[row]) / 2.0),
+        _ => None,
+    }
+}
+
+// The most stones the interactive views draw. Recordings and exports get them all.
+fn drawn_limit(model: &Model) -> usize {
+    if model.settings.cap_drawn && !model.recording {
+        model.settings.max_drawn_stones.max(1)
+    } else {
+        usize::MAX
+    }
+}
+
+// Draw every this many stones to stay within the limit
+fn drawn_stride(total: usize, limit: usize) -> usize {
+    if total > limit { total.div_ceil(limit) } else { 1 }
+}
+
+fn draw_gravel(app: &App, model: &Model, draw: &Draw, rect: Rect, max_stones: usize, cull: bool) {
+    // The offset is in window pixels so shadows fall the same way whatever the grid rotation
+    let grid_draw = |offset: Vec2| grid_transform(model, draw, rect, offset).color_blend(model.settings.blend.component());
+    let gdraw = grid_draw(vec2(0.0, 0.0));
+
+    let [r, g, b] = model.settings.background;
+    if model.settings.trails {
+        // Fade out the previous frame rather than clearing it
+        draw.rect()
+            .wh(rect.wh())
+            .color(srgba(r, g, b, (model.settings.trail_fade * 255.0) as u8));
+    } else {
+        draw.background().color(rgb8(r, g, b));
+    }
+
+    let outline = match &model.stone_shape {
+        Some(shape) => shape.clone(),
+        None => rounded_square(model.settings.corner_radius),
+    };
+
+    // Optionally draw the most displaced stones last so they end up on top. Over the
+    // limit, every Nth stone stands in for the rest while the simulation runs them all.
+    let stride = drawn_stride(model.gravel.len(), max_stones);
+    let mut order: Vec<usize> = (0..model.gravel.len()).step_by(stride).collect();
+    if model.settings.sort_draw {
+        let offset = |index: usize| vec2(model.gravel[index].x_offset, model.gravel[index].y_offset).length();
+        order.sort_by(|&a, &b| offset(a).total_cmp(&offset(b)));
+    }
+    if let Some(row) = model.isolate {
+        order.retain(|&index| model.gravel[index].y as u32 == row);
+    }
+
+    // Graph paper outlines of the undisturbed cells, under the stones
+    if model.settings.show_cells {
+        let [r, g, b] = model.settings.cell_color;
+        for stone in &model.gravel {
+            gdraw.rect()
+                .no_fill()
+                .stroke(rgba8(r, g, b, (model.settings.global_alpha * 255.0) as u8))
+                .stroke_weight(model.settings.cell_weight)
+                .w_h(1.0, 1.0)
+                .x_y(model.col_positions[stone.x as usize], model.row_positions[stone.y as usize])
+                ;
+        }
+    }
+
+    // Dots at the undisturbed grid positions, behind everything else
+    if model.settings.show_anchors {
+        let [r, g, b] = model.settings.anchor_color;
+        for stone in &model.gravel {
+            gdraw.ellipse()
+                .color(rgba8(r, g, b, (model.settings.global_alpha * 255.0) as u8))
+                .w_h(model.settings.anchor_size, model.settings.anchor_size)
+                .x_y(model.col_positions[stone.x as usize], model.row_positions[stone.y as usize])
+                ;
+        }
+    }
+
+    // With cull, skip stones wholly outside rect. However it's turned, a stone stays
+    // within the circle through its cell's corners, so test that around its displaced
+    // center, widened for the outline and shadow. Blur ghosts trail behind, so no
+    // culling while blurring.
+    let blurring = model.motion_blur && model.recording && model.previous_offsets.len() == model.gravel.len();
+    let fit = fit_scale(model, rect);
+    let reach = (0.5 + LINE_WIDTH) * vec2(model.settings.cell_w, model.settings.cell_h).length() * fit + model.settings.shadow_offset.length();
+    let visible = |stone: &Stone, dx: f32, dy: f32| {
+        if !cull || blurring {
+            return true;
+        }
+        let point = grid_to_window(model, vec2(
+            model.col_positions[stone.x as usize] + dx + stone.x_offset,
+            model.row_positions[stone.y as usize] + dy + stone.y_offset,
+        ), fit);
+        point.x >= rect.left() - reach && point.x <= rect.right() + reach
+            && point.y >= rect.bottom() - reach && point.y <= rect.top() + reach
+    };
+
+    // Shadows go down first so no stone is hidden by its neighbour's shadow
+    if model.settings.shadow {
+        let sdraw = grid_draw(model.settings.shadow_offset);
+        for &index in &order {
+            let stone = &model.gravel[index];
+            for (dx, dy) in wrap_shifts(model, stone) {
+                if !visible(stone, dx, dy) {
+                    continue;
+                }
+                let cdraw = sdraw.x_y(model.col_positions[stone.x as usize] + dx, model.row_positions[stone.y as usize] + dy);
+                let rotation = stone.rotation + wave_rotation(model, stone, app.time);
+                let shadow = rgba(0.3, 0.3, 0.3, 0.4 * stone_alpha(model, stone));
+                draw_stone(&cdraw, model, stone, rotation, &outline, shadow);
+            }
+        }
+    }
+
+    for &index in &order {
+        let stone = &model.gravel[index];
+        for (dx, dy) in wrap_shifts(model, stone) {
+            if !visible(stone, dx, dy) {
+                continue;
+            }
+            let cdraw = gdraw.x_y(model.col_positions[stone.x as usize] + dx, model.row_positions[stone.y as usize] + dy);
+            let rotation = stone.rotation + wave_rotation(model, stone, app.time);
+            let stroke = stone_color(model, stone, app.time);
+            if blurring {
+                draw_blur(&cdraw, model, stone, model.previous_offsets[index], &outline, stroke, app.time);
+            }
+            draw_stone(&cdraw, model, stone, rotation, &outline, stroke);
+            if stone.locked && model.settings.show_locks {
+                cdraw.ellipse()
+                    .color(rgba(0.5, 0.5, 0.5, model.settings.global_alpha))
+                    .w_h(0.15, 0.15)
+                    .x_y(stone.x_offset, stone.y_offset)
+                    ;
+            }
+        }
+    }
+
+    // Stones dropped from the grid, on their way out
+    for (home, stone) in &model.leaving {
+        let cdraw = gdraw.x_y(home.x, home.y);
+        draw_stone(&cdraw, model, stone, stone.rotation, &outline, stone_color(model, stone, app.time));
+    }
+}
+
+// Faint copies of a moving stone between where it was before the last step and where
+// it is now, fainter the further back they are.
+fn draw_blur(cdraw: &Draw, model: &Model, stone: &Stone, previous: (Vec2, f32), outline: &[Point2], stroke: Rgba, time: f32) {
+    let (offset, turn) = previous;
+    if offset == vec2(stone.x_offset, stone.y_offset) && turn == stone.rotation {
+        return;
+    }
+    for sample in 1..=model.blur_samples {
+        let t = sample as f32 / (model.blur_samples + 1) as f32;
+        let mut ghost = stone.clone();
+        ghost.x_offset = offset.x + (stone.x_offset - offset.x) * t;
+        ghost.y_offset = offset.y + (stone.y_offset - offset.y) * t;
+        ghost.rotation = turn + (stone.rotation - turn) * t;
+        let rotation = ghost.rotation + wave_rotation(model, stone, time);
+        let faint = rgba(stroke.red, stroke.green, stroke.blue, stroke.alpha * t * 0.5);
+        draw_stone(cdraw, model, &ghost, rotation, outline, faint);
+    }
+}
+
+// Opacity of a stone; when fading at rest, moving stones start out bold and
+// dim as they near their target, and resting stones stay faint.
+fn stone_alpha(model: &Model, stone: &Stone) -> f32 {
+    let alpha = rotation_alpha(model.settings.rot_alpha_scale, stone.rotation) * model.settings.global_alpha;
+    if !model.settings.fade_at_rest {
+        return alpha;
+    }
+    let moving = stone.x_velocity != 0.0 || stone.y_velocity != 0.0 || stone.rot_velocity != 0.0;
+    let rest_alpha = if moving {
+        (stone.cycles as f32 / model.settings.cycle_max as f32).clamp(RESTING_ALPHA, 1.0)
+    } else {
+        RESTING_ALPHA
+    };
+    alpha * rest_alpha
+}
+
+// Drawn size of a stone. A moving stone shrinks by up to scale_dip halfway to its
+// target and grows back as it settles; resting stones keep their full size.
+fn stone_scale(model: &Model, stone: &Stone) -> f32 {
+    let moving = stone.x_velocity != 0.0 || stone.y_velocity != 0.0 || stone.rot_velocity != 0.0;
+    if model.settings.scale_dip == 0.0 || !moving || stone.total_cycles == 0 {
+        return 1.0;
+    }
+    let t = 1.0 - stone.cycles as f32 / stone.total_cycles as f32;
+    1.0 - model.settings.scale_dip * (PI * t).sin()
+}
+
+// Opacity from how far a stone is turned. A positive scale fades stones as they
+// rotate; a negative one fades the straight stones instead, leaving those turned a
+// radian or more fully opaque. Zero leaves every stone opaque.
+fn rotation_alpha(scale: f32, rotation: f32) -> f32 {
+    let turn = rotation.abs();
+    let alpha = if scale >= 0.0 {
+        1.0 - scale * turn
+    } else {
+        1.0 + scale * (1.0 - turn)
+    };
+    alpha.clamp(0.0, 1.0)
+}
+
+// Black, or with color by displacement, the colormap color for how far the
+// stone is from its grid position, reaching the top of the map at color_range.
+// A hue cycle speed, in turns per second, sets the hue turning over time.
+fn stone_color(model: &Model, stone: &Stone, time: f32) -> Rgba {
+    let alpha = stone_alpha(model, stone);
+    let color = if model.settings.color_by_displacement {
+        let magnitude = vec2(stone.x_offset, stone.y_offset).length();
+        let [r, g, b] = model.settings.colormap.color(magnitude / model.settings.color_range);
+        rgb(r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0)
+    } else {
+        rgb(0.0, 0.0, 0.0)
+    };
+    if model.settings.hue_cycle_speed == 0.0 {
+        return rgba(color.red, color.green, color.blue, alpha);
+    }
+    // Black has no hue to turn, so plain stones cycle from a saturated red
+    let base = if model.settings.color_by_displacement { Hsv::from(color) } else { hsv(0.0, 1.0, 1.0) };
+    // Neighbouring stones are a little further round, so the colors sweep across the grid
+    let turns = time * model.settings.hue_cycle_speed + (stone.x + stone.y) / (model.cols + model.rows) as f32;
+    let cycled = Rgb::from(base.shift_hue(360.0 * turns.fract()));
+    rgba(cycled.red, cycled.green, cycled.blue, alpha)
+}
+
+fn draw_stone(cdraw: &Draw, model: &Model, stone: &Stone, rotation: f32, outline: &[Point2], stroke: Rgba) {
+    let size = stone_scale(model, stone);
+    let sdraw = cdraw.x_y(stone.x_offset, stone.y_offset).rotate(rotation).scale(size);
+    // Undo the scale on the line width so shrinking stones keep their outline weight
+    let weight = LINE_WIDTH * (1.0 + model.settings.line_width_jitter * stone.lw_jitter) / size;
+    if model.settings.fill && model.settings.shape != Shape::Line {
+        match &model.texture {
+            // The image is stretched over the cell and cut to the stone's outline
+            Some(texture) => {
+                sdraw.polygon()
+                    .points_textured(texture, outline.iter().map(|&point| (point, point + vec2(0.5, 0.5))));
+            }
+            None => {
+                sdraw.polygon()
+                    .color(stroke)
+                    .points(outline.iter().cloned());
+            }
+        }
+    }
+    if model.settings.shape == Shape::Line {
+        sdraw.line()
+            .start(pt2(-0.5, 0.0))
+            .end(pt2(0.5, 0.0))
+            .weight(weight)
+            .caps(model.settings.line_cap)
+            .color(stroke)
+            ;
+    } else if model.stone_shape.is_some() || model.settings.corner_radius > 0.0 {
+        sdraw.path()
+            .stroke()
+            .weight(weight)
+            .join(model.settings.line_join)
+            .color(stroke)
+            .points_closed(outline.iter().cloned())
+            ;
+    } else {
+        sdraw.rect()
+            .no_fill()
+            .stroke(stroke)
+            .stroke_weight(weight)
+            .join(model.settings.line_join)
+            .w_h(1.0, 1.0)
+            ;
+    }
+}
+
+// Extra rotation for a wave rippling across the grid; the phase of each
+// stone depends on its position along the wave direction.
+fn wave_rotation(model: &Model, stone: &Stone, time: f32) -> f32 {
+    if model.settings.wave_amplitude == 0.0 {
+        return 0.0;
+    }
+    let distance = match model.settings.wave_direction {
+        WaveDirection::Row => stone.y,
+        WaveDirection::Column => stone.x,
+        WaveDirection::Diagonal => stone.x + stone.y,
+        WaveDirection::Radial => {
+            vec2(stone.x - (model.cols - 1) as f32 / 2.0, stone.y - (model.rows - 1) as f32 / 2.0).length()
+        }
+    };
+    let phase = distance * 0.5;
+    model.settings.wave_amplitude * (time * model.settings.wave_speed - phase).sin()
+}
+
+// Grid offsets at which to draw a stone. With wrapping on, a stone that pokes
+// past an edge of the grid is also drawn poking in from the opposite edge.
+fn wrap_shifts(model: &Model, stone: &Stone) -> Vec<(f32, f32)> {
+    let mut shifts = vec![(0.0, 0.0)];
+    if !model.settings.wrap {
+        return shifts;
+    }
+    // Half the diagonal of a unit square, the farthest a rotated stone can reach
+    let reach = 0.5 * 2.0f32.sqrt();
+    let x = model.col_positions[stone.x as usize] + stone.x_offset;
+    let y = model.row_positions[stone.y as usize] + stone.y_offset;
+    let mut x_shifts = vec![0.0];
+    if x - reach < -0.5 {
+        x_shifts.push(model.cols as f32);
+    }
+    if x + reach > model.cols as f32 - 0.5 {
+        x_shifts.push(-(model.cols as f32));
+    }
+    let mut y_shifts = vec![0.0];
+    if y - reach < -0.5 {
+        y_shifts.push(model.rows as f32);
+    }
+    if y + reach > model.rows as f32 - 0.5 {
+        y_shifts.push(-(model.rows as f32));
+    }
+    for &dx in &x_shifts {
+        for &dy in &y_shifts {
+            if dx != 0.0 || dy != 0.0 {
+                shifts.push((dx, dy));
+            }
+        }
+    }
+    shifts
+}
+
+// Outline of a unit square centered on the origin with rounded corners.
+fn rounded_square(radius: f32) -> Vec<Point2> {
+    let inner = 0.5 - radius;
+    let corners = [(inner, inner), (-inner, inner), (-inner, -inner), (inner, -inner)];
+    let mut points = Vec::new();
+    for (i, (cx, cy)) in corners.iter().enumerate() {
+        let start = i as f32 * PI / 2.0;
+        for step in 0..=8 {
+            let angle = start + step as f32 * PI / 16.0;
+            points.push(pt2(cx + radius * angle.cos(), cy + radius * angle.sin()));
+        }
+    }
+    points
+}
+
+// Pie chart showing recording progress, filling clockwise from the top.
+fn draw_clock(draw: &Draw, center: Point2, progress: f32) {
+    let radius = 12.0;
+    let end = progress.min(1.0) * 2.0 * PI;
+    let points = (0..=60).map(|i| {
+        let angle = end * i as f32 / 60.0;
+        center + vec2(angle.sin(), angle.cos()) * radius
+    });
+    draw.polygon()
+        .color(rgba(1.0, 0.0, 0.0, 0.6))
+        .points(std::iter::once(center).chain(points));
+    draw.ellipse()
+        .no_fill()
+        .stroke(RED)
+        .stroke_weight(1.5)
+        .xy(center)
+        .radius(radius);
+}
+
+// Add step degrees to an angle, wrapping into the grid rotation slider's -180 to 180.
+fn step_angle(angle: f32, step: f32) -> f32 {
+    (angle + step + 180.0).rem_euclid(360.0) - 180.0
+}
+
+fn apply_intensity(model: &mut Model, preset: usize) {
+    let (name, disp_adj, rot_adj, motion) = INTENSITY_PRESETS[preset];
+    model.settings.disp_adj = disp_adj;
+    model.settings.rot_adj = rot_adj;
+    model.settings.motion = motion;
+    if model.verbose {
+        println!("intensity {}", name);
+    }
+}
+
+fn key_pressed(app: &App, model: &mut Model, key: Key) {
+    // Let text fields in the control panel have the keys while they are being edited
+    if model.ui.ctx().wants_keyboard_input() {
+        return;
+    }
+    match key {
+        Key::P => save_export(app, model, app.exe_name().unwrap() + ".png"),
+        Key::X => {
+            model.export_preview = Some(export_preview(app, model));
+            // The dialog is in the control panel, so bring it back if it was hidden
+            model.ui_visible = true;
+            if let Some(window) = app.window(model.ui_window) {
+                window.set_visible(true);
+            }
+        }
+        // Turn the whole composition in 15 degree steps, counterclockwise with Q
+        Key::Q => model.settings.grid_rotation = step_angle(model.settings.grid_rotation, 15.0),
+        Key::E => model.settings.grid_rotation = step_angle(model.settings.grid_rotation, -15.0),
+        Key::Y => copy_to_clipboard(app, model),
+        Key::T => {
+            if model.trace {
+                write_trace_svg(model, &(app.exe_name().unwrap() + "_trace.svg"));
+                model.traces.clear();
+            }
+            model.trace = !model.trace;
+        }
+        Key::R => {
+            if model.recording {
+                model.recording = false;
+            } else {
+                match prepare_frames_dir(&model.frames_dir) {
+                    Ok(()) => {
+                        model.recording = true;
+                        model.cur_frame = 0;
+                        // A locked seed makes every recording the same motion
+                        if model.lock_seed {
+                            reseed(model);
+                        }
+                        // Pad frame numbers enough for the whole recording so they sort in order
+                        model.frame_digits = model.max_frames.to_string().len();
+                        model.last_frame_hash = None;
+                    }
+                    Err(error) => {
+                        eprintln!("Problem using directory {:?}: {}", model.frames_dir, error);
+                    }
+                }
+            }
+        }
+        Key::Up => {
+            model.settings.disp_adj += 0.1 * step_scale(app);
+        }
+        Key::Down => {
+            model.settings.disp_adj = (model.settings.disp_adj - 0.1 * step_scale(app)).max(0.0);
+        }
+        Key::Right => {
+            model.settings.rot_adj += 0.1 * step_scale(app);
+        }
+        Key::Left => {
+            model.settings.rot_adj = (model.settings.rot_adj - 0.1 * step_scale(app)).max(0.0);
+        }
+        Key::M => {
+            if model.performance.recording {
+                model.performance.recording = false;
+                save_performance(&model.performance, &model.performance_file);
+            } else {
+                model.performance.events.clear();
+                model.performance.last_values = [f32::NAN; 3];
+                model.performance.start_frame = app.elapsed_frames();
+                model.performance.replaying = false;
+                model.performance.recording = true;
+            }
+        }
+        Key::Period => {
+            if model.performance.replaying {
+                model.performance.replaying = false;
+            } else if !model.performance.recording {
+                if let Some(events) = load_performance(&model.performance_file) {
+                    model.performance.events = events;
+                }
+                model.performance.start_frame = app.elapsed_frames();
+                model.performance.next_event = 0;
+                model.performance.replaying = true;
+            }
+        }
+        Key::C => {
+            model.clock_in_captures = !model.clock_in_captures;
+        }
+        // Show a single row; Home goes back to the full grid
+        Key::PageDown => {
+            model.isolate = Some(model.isolate.map_or(0, |row| (row + 1).min(model.rows - 1)));
+        }
+        Key::PageUp => {
+            model.isolate = Some(model.isolate.map_or(model.rows - 1, |row| row.saturating_sub(1)));
+        }
+        Key::Home => {
+            model.isolate = None;
+        }
+        Key::Key0 => reset_defaults(model),
+        Key::Key1 => apply_intensity(model, 0),
+        Key::Key2 => apply_intensity(model, 1),
+        Key::Key3 => apply_intensity(model, 2),
+        Key::Space => {
+            model.paused = !model.paused;
+        }
+        Key::Return if model.paused => {
+            model.single_step = true;
+        }
+        Key::W => {
+            model.pan.y += PAN_STEP * step_scale(app);
+        }
+        Key::A => {
+            model.pan.x -= PAN_STEP * step_scale(app);
+        }
+        Key::S => {
+            model.pan.y -= PAN_STEP * step_scale(app);
+        }
+        Key::D => {
+            model.pan.x += PAN_STEP * step_scale(app);
+        }
+        Key::O => {
+            let filename = latest_checkpoint(app);
+            resume_session(model, &filename);
+        }
+        Key::L => {
+            // Replay the seed from the start now, and again whenever recording starts
+            model.lock_seed = !model.lock_seed;
+            if model.lock_seed {
+                reseed(model);
+            }
+        }
+        Key::H => {
+            model.ui_visible = !model.ui_visible;
+            if let Some(window) = app.window(model.ui_window) {
+                window.set_visible(model.ui_visible);
+            }
+        }
+        _other_key => {}
+    }
+
+}
+
+// Keyboard adjustments are coarser with Shift held and finer with Ctrl held.
+fn step_scale(app: &App) -> f32 {
+    if app.keys.mods.shift() {
+        5.0
+    } else if app.keys.mods.ctrl() {
+        0.1
+    } else {
+        1.0
+    }
+}
+
+// Create the frames directory (and any missing parents) and make sure we can write to it.
+fn prepare_frames_dir(dir: &str) -> std::io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let probe = Path::new(dir).join(".write_test");
+    fs::write(&probe, "")?;
+    fs::remove_file(probe)
+}
+
+fn mouse_pressed(app: &App, model: &mut Model, button: MouseButton) {
+    if button == MouseButton::Left && app.keys.mods.shift() {
+        let fit = fit_scale(model, app.window_rect());
+        if let Some(index) = stone_at(model, (app.mouse.position() + model.pan) / fit) {
+            model.gravel[index].locked = !model.gravel[index].locked;
+        }
+    } else if button == MouseButton::Left {
+        model.lasso = vec![app.mouse.position()];
+    }
+}
+
+// Dragging with the left button draws a lasso around the stones to select.
+fn mouse_moved(app: &App, model: &mut Model, position: Point2) {
+    if !model.lasso.is_empty() && app.mouse.buttons.left().is_down() {
+        model.lasso.push(position);
+    }
+}
+
+// Select the stones whose centers are inside the lasso. A click without a drag
+// clears the selection.
+fn mouse_released(app: &App, model: &mut Model, button: MouseButton) {
+    if button != MouseButton::Left || model.lasso.is_empty() {
+        return;
+    }
+    let lasso = std::mem::take(&mut model.lasso);
+    let fit = fit_scale(model, app.window_rect());
+    model.selection = if lasso.len() < 3 {
+        Vec::new()
+    } else {
+        model.gravel.iter()
+            .enumerate()
+            .filter(|(_, stone)| inside_polygon(stone_window_position(model, stone, fit), &lasso))
+            .map(|(index, _)| index)
+            .collect()
+    };
+}
+
+// Where a stone's center is in window coordinates; the reverse of stone_at().
+fn stone_window_position(model: &Model, stone: &Stone, fit: f32) -> Point2 {
+    let point = vec2(model.col_positions[stone.x as usize] + stone.x_offset, model.row_positions[stone.y as usize] + stone.y_offset);
+    grid_to_window(model, point, fit)
+}
+
+// A point in grid units, as drawn through grid_transform(), in window coordinates.
+fn grid_to_window(model: &Model, point: Vec2, fit: f32) -> Point2 {
+    let x = (point.x - model.cols as f32 / 2.0 + 0.5) * model.settings.cell_w;
+    let y = -(point.y - model.rows as f32 / 2.0 + 0.5) * model.settings.cell_h;
+    let (sin, cos) = deg_to_rad(model.settings.grid_rotation).sin_cos();
+    pt2(x * cos - y * sin, x * sin + y * cos) * fit - model.pan
+}
+
+// Even-odd test: a point is inside if a ray from it crosses the edges an odd number of times.
+fn inside_polygon(point: Point2, polygon: &[Point2]) -> bool {
+    let mut inside = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for &current in polygon {
+        if (current.y > point.y) != (previous.y > point.y)
+            && point.x < previous.x + (point.y - previous.y) / (current.y - previous.y) * (current.x - previous.x)
+        {
+            inside = !inside;
+        }
+        previous = current;
+    }
+    inside
+}
+
+fn apply_group_op(model: &mut Model, op: GroupOp) {
+    for &index in &model.selection {
+        let stone = &mut model.gravel[index];
+        match op {
+            GroupOp::Reset => {
+                stone.x_offset = 0.0;
+                stone.y_offset = 0.0;
+                stone.rotation = 0.0;
+                stone.x_velocity = 0.0;
+                stone.y_velocity = 0.0;
+                stone.rot_velocity = 0.0;
+                stone.cycles = 0;
+            }
+            GroupOp::Lock => stone.locked = true,
+            GroupOp::Unlock => stone.locked = false,
+            GroupOp::Nudge(step) => {
+                stone.x_offset += step.x;
+                stone.y_offset += step.y;
+            }
+        }
+    }
+}
+
+// How much to scale the grid so it and its margin fill the view. The border then
+// stays in proportion when the window is resized; at the starting size this is 1.
+fn fit_scale(model: &Model, rect: Rect) -> f32 {
+    let w = model.cols as f32 * model.settings.cell_w + 2.0 * MARGIN as f32;
+    let h = model.rows as f32 * model.settings.cell_h + 2.0 * MARGIN as f32;
+    (rect.w() / w).min(rect.h() / h)
+}
+
+// Find the index of the stone whose grid cell contains a point in window coordinates.
+fn stone_at(model: &Model, point: Point2) -> Option<usize> {
+    let (sin, cos) = deg_to_rad(-model.settings.grid_rotation).sin_cos();
+    let point = pt2(point.x * cos - point.y * sin, point.x * sin + point.y * cos);
+    let x = point.x / model.settings.cell_w + model.cols as f32 / 2.0 - 0.5;
+    let y = -point.y / model.settings.cell_h + model.rows as f32 / 2.0 - 0.5;
+    if x < -0.5 || y < -0.5 || x >= model.cols as f32 - 0.5 || y >= model.rows as f32 - 0.5 {
+        return None;
+    }
+    let x = nearest(&model.col_positions, x)?;
+    let y = nearest(&model.row_positions, y)?;
+    Some(y * model.cols as usize + x)
+}
+
+// Index of the position closest to value
+fn nearest(positions: &[f32], value: f32) -> Option<usize> {
+    positions.iter()
+        .enumerate()
+        .min_by(|a, b| (a.1 - value).abs().total_cmp(&(b.1 - value).abs()))
+        .map(|(index, _)| index)
+}
+
+fn ui_view(_app: &App, model: &Model, frame: Frame) {
+    model.ui.draw_to_frame(&frame).unwrap();
+}
+
+fn raw_ui_event(_app: &App, model: &mut Model, event: &nannou::winit::event::WindowEvent) {
+    model.ui.handle_raw_event(event);
+}
+
+// Put every tunable parameter back to how the program starts, and start the
+// stones over. Output settings such as the frames directory are left alone.
+fn reset_defaults(model: &mut Model) {
+    model.pan = vec2(0.0, 0.0);
+    model.settings = Settings::default();
+    // The cell size may have changed, so fit the window to it again
+    model.resize_window = true;
+    model.col_positions = model.settings.spacing.positions(model.cols);
+    model.row_positions = model.settings.spacing.positions(model.rows);
+    model.isolate = None;
+    reseed(model);
+}
+
+// Add each stone's position to its path, skipping stones that haven't moved.
+// Paths stop growing at MAX_TRACE_SAMPLES to bound memory.
+fn record_traces(model: &mut Model) {
+    model.traces.resize_with(model.gravel.len(), Vec::new);
+    for (stone, trace) in model.gravel.iter().zip(&mut model.traces) {
+        let point = vec2(stone.x_offset, stone.y_offset);
+        if trace.len() < MAX_TRACE_SAMPLES && trace.last() != Some(&point) {
+            trace.push(point);
+        }
+    }
+}
+
+// The smallest size with the given aspect ratio that holds all of a w by h view.
+fn padded_size((w, h): (f32, f32), ratio: f32) -> [u32; 2] {
+    if w / h < ratio {
+        [(h * ratio).round() as u32, h as u32]
+    } else {
+        [w as u32, (w / ratio).round() as u32]
+    }
+}
+
+// What export_all() would write right now, for the confirmation dialog.
+fn export_preview(app: &App, model: &Model) -> ExportPreview {
+    let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+    let base = format!("{}_{}", app.exe_name().unwrap(), seconds);
+    let size = export_size(app, model);
+    ExportPreview { base, size }
+}
+
+// Size of an exported PNG. What's on screen is the window's size; the whole
+// composition is the grid and margin at export_scale. Either is padded out to
+// the export aspect ratio if one is chosen.
+fn export_size(app: &App, model: &Model) -> [u32; 2] {
+    let (w, h) = if model.export_wysiwyg {
+        match app.window(model.main_window) {
+            Some(window) if model.export_aspect.ratio().is_none() => {
+                let (w, h) = window.inner_size_pixels();
+                return [w, h];
+            }
+            Some(window) => window.rect().w_h(),
+            None => return [0, 0],
+        }
+    } else {
+        let w = model.cols as f32 * model.settings.cell_w + 2.0 * MARGIN as f32;
+        let h = model.rows as f32 * model.settings.cell_h + 2.0 * MARGIN as f32;
+        (w * model.export_scale, h * model.export_scale)
+    };
+    match model.export_aspect.ratio() {
+        Some(ratio) => padded_size((w, h), ratio),
+        None => [w.round() as u32, h.round() as u32],
+    }
+}
+
+// Save the arrangement as PNG, SVG and JSON under one name so the raster, vector
+// and data versions always match.
+fn export_all(app: &App, model: &Model, base: &str) {
+    save_export(app, model, format!("{}.png", base));
+    write_svg(app, model, &format!("{}.svg", base));
+    save_session(model, &format!("{}.json", base));
+}
+
+// Write the stones as they are drawn now, one outline per stone.
+fn write_svg(app: &App, model: &Model, filename: &str) {
+    let outline = match &model.stone_shape {
+        _ if model.settings.shape == Shape::Line => vec![pt2(-0.5, 0.0), pt2(0.5, 0.0)],
+        Some(shape) => shape.clone(),
+        None if model.settings.corner_radius > 0.0 => rounded_square(model.settings.corner_radius),
+        None => vec![pt2(0.5, 0.5), pt2(-0.5, 0.5), pt2(-0.5, -0.5), pt2(0.5, -0.5)],
+    };
+    let element = if model.settings.shape == Shape::Line { "polyline" } else { "polygon" };
+
+    // Each row is its own Inkscape layer so rows can be picked out and edited
+    let mut svg = svg_header(model);
+    for row in 0..model.rows {
+        svg += &format!("<g id=\"row{}\" inkscape:groupmode=\"layer\" inkscape:label=\"Row {}\" fill=\"none\">\n", row + 1, row + 1);
+        for stone in model.gravel.iter().filter(|stone| stone.y as u32 == row) {
+            svg += &svg_stone(app, model, stone, &outline, element);
+        }
+        svg += "</g>\n";
+    }
+    svg += "</svg>\n";
+    if let Err(error) = fs::write(filename, svg) {
+        eprintln!("Problem writing {:?}: {}", filename, error);
+    }
+}
+
+// One stone as an SVG element of the given kind, following its outline.
+fn svg_stone(app: &App, model: &Model, stone: &Stone, outline: &[Point2], element: &str) -> String {
+    let (sin, cos) = (stone.rotation + wave_rotation(model, stone, app.time)).sin_cos();
+    let points: Vec<String> = outline.iter().map(|point| {
+        let turned = vec2(point.x * cos - point.y * sin, point.x * sin + point.y * cos);
+        svg_point(model, stone, vec2(stone.x_offset, stone.y_offset) + turned)
+    }).collect();
+    let color = stone_color(model, stone, app.time);
+    let weight = LINE_WIDTH * (1.0 + model.settings.line_width_jitter * stone.lw_jitter) * model.settings.cell_w;
+    format!("<{} points=\"{}\" stroke=\"rgb({},{},{})\" stroke-opacity=\"{:.2}\" stroke-width=\"{:.2}\"/>\n",
+        element,
+        points.join(" "),
+        (color.red * 255.0) as u8,
+        (color.green * 255.0) as u8,
+        (color.blue * 255.0) as u8,
+        color.alpha,
+        weight)
+}
+
+// Opening tag of an SVG the size of the window at the current cell size
+fn svg_header(model: &Model) -> String {
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:inkscape=\"http://www.inkscape.org/namespaces/inkscape\" width=\"{}\" height=\"{}\">\n",
+        model.cols as f32 * model.settings.cell_w + 2.0 * MARGIN as f32,
+        model.rows as f32 * model.settings.cell_h + 2.0 * MARGIN as f32)
+}
+
+// SVG coordinates of a stone's home cell plus an offset in grid units
+fn svg_point(model: &Model, stone: &Stone, offset: Vec2) -> String {
+    let home = vec2(model.col_positions[stone.x as usize], model.row_positions[stone.y as usize]);
+    let point = home + offset + vec2(0.5, 0.5);
+    format!("{:.2},{:.2}",
+        MARGIN as f32 + point.x * model.settings.cell_w,
+        MARGIN as f32 + point.y * model.settings.cell_h)
+}
+
+// Write the traced paths as an SVG the size of the window, one path per stone.
+fn write_trace_svg(model: &Model, filename: &str) {
+    let mut svg = svg_header(model);
+    svg += "<g fill=\"none\" stroke=\"black\" stroke-width=\"0.5\">\n";
+    for (stone, trace) in model.gravel.iter().zip(&model.traces) {
+        if trace.is_empty() {
+            continue;
+        }
+        let points: Vec<String> = trace.iter().map(|offset| svg_point(model, stone, *offset)).collect();
+        svg += &format!("<path d=\"M {}\"/>\n", points.join(" L "));
+    }
+    svg += "</g>\n</svg>\n";
+    if let Err(error) = fs::write(filename, svg) {
+        eprintln!("Problem writing {:?}: {}", filename, error);
+    }
+}
+
+// Count the stones' offset magnitudes into HISTOGRAM_BINS equal bins from zero
+// to the largest offset.
+fn update_histogram(model: &mut Model) {
+    let magnitudes: Vec<f32> = model.gravel.iter()
+        .map(|stone| vec2(stone.x_offset, stone.y_offset).length())
+        .collect();
+    let max = magnitudes.iter().cloned().fold(0.0, f32::max).max(0.001);
+    model.histogram = vec![0; HISTOGRAM_BINS];
+    for magnitude in magnitudes {
+        let bin = ((magnitude / max * HISTOGRAM_BINS as f32) as usize).min(HISTOGRAM_BINS - 1);
+        model.histogram[bin] += 1;
+    }
+    model.histogram_max = max;
+}
+
+// Make the panel's text and widgets bigger or smaller. nannou_egui always renders at
+// the window's scale factor, so egui's pixels_per_point can't be used for this.
+fn scale_egui(ctx: &egui::CtxRef, scale: f32) {
+    let mut fonts = egui::FontDefinitions::default();
+    for (_family, size) in fonts.family_and_size.values_mut() {
+        *size *= scale;
+    }
+    ctx.set_fonts(fonts);
+
+    let mut style = (*ctx.style()).clone();
+    let spacing = &mut style.spacing;
+    spacing.item_spacing *= scale;
+    spacing.window_padding *= scale;
+    spacing.button_padding *= scale;
+    spacing.indent *= scale;
+    spacing.interact_size *= scale;
+    spacing.slider_width *= scale;
+    spacing.text_edit_width *= scale;
+    spacing.icon_width *= scale;
+    spacing.icon_spacing *= scale;
+    ctx.set_style(style);
+}
+
+// Start the stones over from the grid with the motion RNG reset to model.seed.
+fn reseed(model: &mut Model) {
+    model.rng = ChaCha12Rng::seed_from_u64(model.seed);
+    model.seed_text = model.seed.to_string();
+    model.steps = 0;
+    model.drift_phase = 0.0;
+    model.leaving.clear();
+    // Locked stones keep their place, rotation and look through a reseed
+    let previous = model.gravel.clone();
+    for stone in &mut model.gravel {
+        *stone = Stone::new(stone.x, stone.y);
+    }
+    assign_line_jitter(&mut model.gravel, model.seed);
+    assign_restlessness(&mut model.gravel, model.seed);
+    for (stone, old) in model.gravel.iter_mut().zip(previous) {
+        if old.locked {
+            *stone = old;
+        }
+    }
+}
+
+// Switch to model.seed without reseed()'s jump back to the grid. Each stone heads from
+// where it is to the first target a fresh start on the seed would give it, arriving on
+// the same step, so from then on the motion is exactly what reseed() would produce.
+fn retarget(model: &mut Model) {
+    let previous = model.gravel.clone();
+    reseed(model);
+    // Take the first step now so the new targets are known
+    move_stones(model);
+    model.drift_phase += model.settings.drift_speed as f64;
+    model.steps += 1;
+    for (stone, old) in model.gravel.iter_mut().zip(&previous) {
+        if stone.locked {
+            continue;
+        }
+        let cycles = stone.cycles as f32;
+        stone.x_velocity = (stone.x_offset + stone.x_velocity * cycles - old.x_offset) / cycles;
+        stone.y_velocity = (stone.y_offset + stone.y_velocity * cycles - old.y_offset) / cycles;
+        stone.rot_velocity = (stone.rotation + stone.rot_velocity * cycles - old.rotation) / cycles;
+        stone.x_offset = old.x_offset;
+        stone.y_offset = old.y_offset;
+        stone.rotation = old.rotation;
+    }
+}
+
+// Give each stone a fixed random amount, from -1.0 to 1.0, by which its line width
+// varies. It comes from its own RNG so the motion for a seed is unchanged.
+fn assign_line_jitter(gravel: &mut [Stone], seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(1));
+    for stone in gravel {
+        stone.lw_jitter = rng.gen_range(-1.0..=1.0);
+    }
+}
+
+// Give each stone a fixed restlessness, from -1.0 to 1.0, that raises or lowers its
+// chance of moving. Like the line jitter it has its own RNG.
+fn assign_restlessness(gravel: &mut [Stone], seed: u64) {
+    let mut rng = StdRng::seed_from_u64(seed.wrapping_add(2));
+    for stone in gravel {
+        stone.restlessness = rng.gen_range(-1.0..=1.0);
+    }
+}
+
+// A checkbox to clamp offsets on one axis and, when checked, a slider for the limit
+// in cells. Unchecked, the limit is infinite.
+fn offset_limit(ui: &mut egui::Ui, limit: &mut f32, text: &str) {
+    let mut clamped = limit.is_finite();
+    if ui.checkbox(&mut clamped, text).changed() {
+        *limit = if clamped { 1.0 } else { f32::INFINITY };
+    }
+    if clamped {
+        ui.add(egui::Slider::new(limit, 0.0..=2.0));
+    }
+}
+
+fn update_ui(model: &mut Model) {
+    let ctx = model.ui.begin_frame();
+    let mut new_seed = None;
+    let mut reset = false;
+    let mut group_op = None;
+    egui::Window::new("Schotter Control Panel").collapsible(false).show(&ctx, |ui| {
+        // The panel is taller than most screens, so it scrolls inside a window of fixed height
+        egui::ScrollArea::vertical().show(ui, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Randomize").clicked() {
+                    new_seed = Some(random::<u64>());
+                }
+                ui.label("Seed");
+                if ui.text_edit_singleline(&mut model.seed_text).lost_focus() {
+                    match model.seed_text.trim().parse() {
+                        Ok(seed) => new_seed = Some(seed),
+                        Err(_) => model.seed_text = model.seed.to_string(),
+                    }
+                }
+            });
+            if ui.button("Reset to defaults").clicked() {
+                reset = true;
+            }
+            ui.checkbox(&mut model.settings.smooth_reseed, "Glide to new seeds");
+            ui.add(egui::Slider::new(&mut model.settings.disp_adj, 0.0..=5.0).text("Displacement"));
+            ui.add(egui::Slider::new(&mut model.settings.rot_adj, 0.0..=5.0).text("Rotation"));
+            ui.add(egui::Slider::new(&mut model.settings.rot_bias, -1.0..=1.0).text("Rotation bias"));
+            ui.add(egui::Slider::new(&mut model.settings.factor_gamma, 0.2..=5.0).text("Contrast"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.settings.factor_shape, FactorShape::Linear, "Ramp");
+                ui.radio_value(&mut model.settings.factor_shape, FactorShape::Peak, "Peak");
+                ui.add_enabled(model.settings.factor_shape == FactorShape::Peak,
+                    egui::Slider::new(&mut model.settings.peak, 0.05..=0.95).text("at"));
+            });
+            ui.add(egui::Slider::new(&mut model.settings.motion, 0.0..=1.0).text("Motion"));
+            ui.add(egui::Slider::new(&mut model.settings.restlessness, 0.0..=1.0).text("Restlessness spread"));
+            ui.checkbox(&mut model.fixed_rate, "Same speed at any frame rate");
+            ui.horizontal(|ui| offset_limit(ui, &mut model.settings.max_offset_x, "Max X offset"));
+            ui.horizontal(|ui| offset_limit(ui, &mut model.settings.max_offset_y, "Max Y offset"));
+            ui.add(egui::Slider::new(&mut model.settings.cycle_min, 1..=999).text("Cycle min"));
+            ui.add(egui::Slider::new(&mut model.settings.cycle_max, 1..=1000).text("Cycle max"));
+            // random_range() needs a non-empty range; the minimum stops short of the
+            // maximum's top so the guard keeps cycle_max within its slider
+            model.settings.cycle_min = model.settings.cycle_min.min(999);
+            model.settings.cycle_max = model.settings.cycle_max.max(model.settings.cycle_min + 1);
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.settings.easing, EasingKind::Linear, "Linear");
+                ui.radio_value(&mut model.settings.easing, EasingKind::EaseIn, "In");
+                ui.radio_value(&mut model.settings.easing, EasingKind::EaseOut, "Out");
+                ui.radio_value(&mut model.settings.easing, EasingKind::EaseInOut, "In-out");
+            });
+            let easing = model.settings.easing;
+            let curve = (0..=50).map(|i| {
+                let t = i as f32 / 50.0;
+                egui::plot::Value::new(t, easing.apply(t))
+            });
+            ui.add(egui::plot::Plot::new("easing")
+                .line(egui::plot::Line::new(egui::plot::Values::from_values_iter(curve)))
+                .height(60.0)
+                .allow_drag(false)
+                .allow_zoom(false));
+            ui.add(egui::Slider::new(&mut model.settings.separation_strength, 0.0..=1.0).text("Separation"));
+            ui.checkbox(&mut model.settings.noise, "Noise displacement");
+            ui.add_enabled_ui(model.settings.noise, |ui| {
+                ui.add(egui::Slider::new(&mut model.settings.noise_octaves, 1..=8).text("Octaves"));
+                ui.add(egui::Slider::new(&mut model.settings.noise_lacunarity, 1.0..=4.0).text("Lacunarity"));
+                ui.add(egui::Slider::new(&mut model.settings.noise_persistence, 0.0..=1.0).text("Persistence"));
+                ui.horizontal(|ui| {
+                    if ui.button("Randomize").clicked() {
+                        model.noise_seed = random::<u32>();
+                    }
+                    ui.add(egui::DragValue::new(&mut model.noise_seed));
+                    ui.label("Noise seed");
+                });
+                ui.add(egui::Slider::new(&mut model.settings.drift_speed, 0.0..=0.05).text("Drift"));
+            });
+            ui.checkbox(&mut model.settings.audio_enabled, "Audio input");
+            ui.add_enabled_ui(model.settings.audio_enabled, |ui| {
+                ui.horizontal(|ui| {
+                    ui.radio_value(&mut model.settings.audio_target, AudioTarget::Motion, "Motion");
+                    ui.radio_value(&mut model.settings.audio_target, AudioTarget::Displacement, "Displacement");
+                });
+                ui.add(egui::Slider::new(&mut model.settings.audio_sensitivity, 0.0..=50.0).text("Sensitivity"));
+            });
+            ui.checkbox(&mut model.settings.show_locks, "Show locked stones");
+            ui.horizontal(|ui| {
+                ui.set_enabled(!model.selection.is_empty());
+                ui.label(format!("{} selected", model.selection.len()));
+                if ui.button("Reset").clicked() {
+                    group_op = Some(GroupOp::Reset);
+                }
+                if ui.button("Lock").clicked() {
+                    group_op = Some(GroupOp::Lock);
+                }
+                if ui.button("Unlock").clicked() {
+                    group_op = Some(GroupOp::Unlock);
+                }
+                if ui.button("Clear").clicked() {
+                    model.selection.clear();
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.set_enabled(!model.selection.is_empty());
+                ui.label("Nudge");
+                // Grid y runs down the window
+                for (label, step) in [("Left", vec2(-0.1, 0.0)), ("Up", vec2(0.0, -0.1)), ("Down", vec2(0.0, 0.1)), ("Right", vec2(0.1, 0.0))] {
+                    if ui.button(label).clicked() {
+                        group_op = Some(GroupOp::Nudge(step));
+                    }
+                }
+            });
+            ui.checkbox(&mut model.save_session, "Save session on exit");
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.settings.shape, Shape::Square, "Squares");
+                ui.radio_value(&mut model.settings.shape, Shape::Line, "Lines");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Joins");
+                ui.radio_value(&mut model.settings.line_join, LineJoin::Miter, "Miter");
+                ui.radio_value(&mut model.settings.line_join, LineJoin::Round, "Round");
+                ui.radio_value(&mut model.settings.line_join, LineJoin::Bevel, "Bevel");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Caps");
+                ui.radio_value(&mut model.settings.line_cap, LineCap::Butt, "Butt");
+                ui.radio_value(&mut model.settings.line_cap, LineCap::Square, "Square");
+                ui.radio_value(&mut model.settings.line_cap, LineCap::Round, "Round");
+            });
+            ui.add(egui::Slider::new(&mut model.settings.corner_radius, 0.0..=0.5).text("Corner radius"));
+            ui.add(egui::Slider::new(&mut model.settings.scale_dip, 0.0..=0.9).text("Scale dip"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.settings.fill, "Fill");
+                ui.text_edit_singleline(&mut model.texture_path);
+                if ui.button("Load texture").clicked() {
+                    model.texture_requested = true;
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Blend");
+                ui.radio_value(&mut model.settings.blend, BlendMode::Normal, "Normal");
+                ui.radio_value(&mut model.settings.blend, BlendMode::Additive, "Add");
+                ui.radio_value(&mut model.settings.blend, BlendMode::Multiply, "Multiply");
+                ui.color_edit_button_srgb(&mut model.settings.background);
+            });
+            let cell_w = ui.add(egui::Slider::new(&mut model.settings.cell_w, 10.0..=60.0).text("Cell width"));
+            let cell_h = ui.add(egui::Slider::new(&mut model.settings.cell_h, 10.0..=60.0).text("Cell height"));
+            if cell_w.changed() || cell_h.changed() {
+                model.resize_window = true;
+            }
+            ui.add(egui::Slider::new(&mut model.settings.target_rows, 1..=40).text("Rows"));
+            ui.add(egui::Slider::new(&mut model.settings.target_cols, 1..=30).text("Columns"));
+            ui.horizontal(|ui| {
+                ui.label("Spacing");
+                let uniform = ui.radio_value(&mut model.settings.spacing, Spacing::Uniform, "Uniform");
+                let log = ui.radio_value(&mut model.settings.spacing, Spacing::Logarithmic, "Logarithmic");
+                if uniform.changed() || log.changed() {
+                    model.col_positions = model.settings.spacing.positions(model.cols);
+                    model.row_positions = model.settings.spacing.positions(model.rows);
+                }
+            });
+            ui.add(egui::Slider::new(&mut model.settings.global_alpha, 0.0..=1.0).text("Opacity"));
+            ui.add(egui::Slider::new(&mut model.settings.rot_alpha_scale, -2.0..=2.0).text("Rotation fade"));
+            ui.add(egui::Slider::new(&mut model.settings.line_width_jitter, 0.0..=0.9).text("Line width jitter"));
+            ui.checkbox(&mut model.settings.fade_at_rest, "Fade resting stones");
+            ui.checkbox(&mut model.settings.sort_draw, "Displaced stones on top");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.settings.cap_drawn, "Cap drawn stones");
+                ui.add_enabled(model.settings.cap_drawn,
+                    egui::Slider::new(&mut model.settings.max_drawn_stones, 10..=(model.rows * model.cols).max(10) as usize));
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.settings.show_anchors, "Grid dots");
+                ui.color_edit_button_srgb(&mut model.settings.anchor_color);
+            });
+            ui.add_enabled(model.settings.show_anchors, egui::Slider::new(&mut model.settings.anchor_size, 0.02..=0.5).text("Dot size"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.settings.show_cells, "Cell borders");
+                ui.color_edit_button_srgb(&mut model.settings.cell_color);
+            });
+            ui.add_enabled(model.settings.show_cells, egui::Slider::new(&mut model.settings.cell_weight, 0.005..=0.1).text("Border weight"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.settings.show_threshold, "Chaos threshold");
+                ui.checkbox(&mut model.threshold_in_captures, "In captures");
+            });
+            ui.add_enabled(model.settings.show_threshold, egui::Slider::new(&mut model.settings.threshold, 0.01..=0.5).text("Threshold"));
+            ui.checkbox(&mut model.settings.shadow, "Shadows");
+            ui.add_enabled_ui(model.settings.shadow, |ui| {
+                ui.add(egui::Slider::new(&mut model.settings.shadow_offset.x, -10.0..=10.0).text("Shadow x"));
+                ui.add(egui::Slider::new(&mut model.settings.shadow_offset.y, -10.0..=10.0).text("Shadow y"));
+            });
+            ui.checkbox(&mut model.settings.wrap, "Wrap around edges");
+            ui.add(egui::Slider::new(&mut model.settings.grid_rotation, -180.0..=180.0).text("Grid rotation"));
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.settings.trails, "Trails");
+                ui.add(egui::Slider::new(&mut model.settings.trail_fade, 0.01..=1.0).text("Fade"));
+            });
+            ui.add(egui::Slider::new(&mut model.settings.wave_amplitude, 0.0..=1.0).text("Wave amplitude"));
+            ui.add(egui::Slider::new(&mut model.settings.wave_speed, 0.0..=10.0).text("Wave speed"));
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.settings.wave_direction, WaveDirection::Row, "Row");
+                ui.radio_value(&mut model.settings.wave_direction, WaveDirection::Column, "Column");
+                ui.radio_value(&mut model.settings.wave_direction, WaveDirection::Diagonal, "Diagonal");
+                ui.radio_value(&mut model.settings.wave_direction, WaveDirection::Radial, "Radial");
+            });
+            ui.horizontal(|ui| {
+                ui.label("Export");
+                ui.radio_value(&mut model.export_aspect, ExportAspect::Native, "Window");
+                ui.radio_value(&mut model.export_aspect, ExportAspect::Square, "Square");
+                ui.radio_value(&mut model.export_aspect, ExportAspect::Wide, "16:9");
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.export_aspect, ExportAspect::A4Portrait, "A4 portrait");
+                ui.radio_value(&mut model.export_aspect, ExportAspect::A4Landscape, "A4 landscape");
+            });
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.export_wysiwyg, "As on screen");
+                ui.add_enabled(!model.export_wysiwyg,
+                    egui::Slider::new(&mut model.export_scale, 0.5..=4.0).text("Export scale"));
+            });
+            ui.add_enabled_ui(!model.recording, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Frames");
+                    ui.radio_value(&mut model.capture_format, CaptureFormat::Png, "PNG");
+                    ui.radio_value(&mut model.capture_format, CaptureFormat::Jpeg, "JPEG");
+                });
+            });
+            ui.checkbox(&mut model.skip_duplicate_frames, "Skip unchanged frames");
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut model.motion_blur, "Motion blur");
+                ui.add_enabled(model.motion_blur, egui::Slider::new(&mut model.blur_samples, 1..=8).text("Samples"));
+            });
+            ui.add_enabled(model.capture_format == CaptureFormat::Jpeg,
+                egui::Slider::new(&mut model.jpeg_quality, 1..=100).text("JPEG quality"));
+            ui.horizontal(|ui| {
+                ui.label("Output");
+                ui.add_enabled(!model.recording, egui::TextEdit::singleline(&mut model.frames_dir));
+            });
+            ui.horizontal(|ui| {
+                ui.radio_value(&mut model.settings.colormap, Colormap::Grayscale, "Grayscale");
+                ui.radio_value(&mut model.settings.colormap, Colormap::Heat, "Heat");
+                ui.radio_value(&mut model.settings.colormap, Colormap::CoolWarm, "Cool-warm");
+            });
+            ui.horizontal(|ui| {
+                if ui.button("Density map").clicked() {
+                    model.export_density = true;
+                }
+                ui.checkbox(&mut model.settings.color_by_displacement, "Color stones");
+            });
+            ui.add_enabled(model.settings.color_by_displacement,
+                egui::Slider::new(&mut model.settings.color_range, 0.1..=3.0).text("Color range"));
+            ui.add(egui::Slider::new(&mut model.settings.hue_cycle_speed, -1.0..=1.0).text("Hue cycle"));
+            ui.label(format!("Offsets (up to {:.2} cells)", model.histogram_max));
+            let width = model.histogram_max as f64 / HISTOGRAM_BINS as f64;
+            let steps = model.histogram.iter().enumerate().flat_map(|(i, &count)| {
+                [
+                    egui::plot::Value::new(i as f64 * width, count),
+                    egui::plot::Value::new((i + 1) as f64 * width, count),
+                ]
+            });
+            ui.add(egui::plot::Plot::new("histogram")
+                .line(egui::plot::Line::new(egui::plot::Values::from_values_iter(steps)).fill(0.0))
+                .height(60.0)
+                .allow_drag(false)
+                .allow_zoom(false));
+        });
+    });
+    let mut export = None;
+    if let Some(preview) = &model.export_preview {
+        egui::Window::new("Export").collapsible(false).resizable(false).show(&ctx, |ui| {
+            ui.label(format!("{} x {} pixels", preview.size[0], preview.size[1]));
+            for extension in ["png", "svg", "json"] {
+                ui.label(format!("{}.{}", preview.base, extension));
+            }
+            let exists = ["png", "svg", "json"].iter()
+                .any(|extension| Path::new(&format!("{}.{}", preview.base, extension)).exists());
+            if exists {
+                ui.colored_label(egui::Color32::RED, "Existing files will be overwritten");
+            }
+            ui.horizontal(|ui| {
+                if ui.button("Confirm").clicked() {
+                    export = Some(true);
+                }
+                if ui.button("Cancel").clicked() {
+                    export = Some(false);
+                }
+            });
+        });
+    }
+    // The frame has to end before the model can be borrowed again
+    drop(ctx);
+    match export {
+        Some(true) => model.export_confirmed = true,
+        Some(false) => model.export_preview = None,
+        None => {}
+    }
+    if let Some(seed) = new_seed {
+        model.seed = seed;
+        if model.settings.smooth_reseed {
+            retarget(model);
+        } else {
+            reseed(model);
+        }
+    }
+    if let Some(op) = group_op {
+        apply_group_op(model, op);
+    }
+    if reset {
+        reset_defaults(model);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separate_leaves_locked_stones_alone() {
+        let mut gravel = vec![Stone::new(0.0, 0.0), Stone::new(1.0, 0.0)];
+        gravel[0].locked = true;
+        gravel[0].x_offset = 0.1;
+        gravel[1].x_offset = -0.5;
+        separate(&mut gravel, 1.0);
+
+        assert_eq!((gravel[0].x_offset, gravel[0].y_offset), (0.1, 0.0));
+        // The free stone is pushed the whole way out of the overlap
+        let gap = (gravel[1].x + gravel[1].x_offset) - (gravel[0].x + gravel[0].x_offset);
+        assert!((gap - 1.0).abs() < 1e-6, "{} != 1.0", gap);
+    }
+}
@@ -0,0 +1,168 @@
+use nannou::noise::{Fbm, NoiseFn};
+use nannou::prelude::*;
+use nannou::rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Stone {
+    pub x: f32,
+    pub y: f32,
+    pub x_offset: f32,
+    pub y_offset: f32,
+    pub rotation: f32,
+    pub x_velocity: f32,
+    pub y_velocity: f32,
+    pub rot_velocity: f32,
+    pub cycles: u32,
+    pub total_cycles: u32,
+    // Sessions saved before these were added leave them out
+    #[serde(default)]
+    pub lw_jitter: f32,
+    #[serde(default)]
+    pub restlessness: f32,
+    #[serde(default)]
+    pub locked: bool,
+}
+
+impl Stone {
+    pub fn new(x: f32, y: f32) -> Self {
+        let x_offset = 0.0;
+        let y_offset = 0.0;
+        let rotation = 0.0;
+        let x_velocity = 0.0;
+        let y_velocity = 0.0;
+        let rot_velocity = 0.0;
+        let cycles = 0;
+        let total_cycles = 0;
+        let lw_jitter = 0.0;
+        let restlessness = 0.0;
+        let locked = false;
+        Stone {
+            x,
+            y,
+            x_offset,
+            y_offset,
+            rotation,
+            x_velocity,
+            y_velocity,
+            rot_velocity,
+            cycles,
+            total_cycles,
+            lw_jitter,
+            restlessness,
+            locked,
+        }
+    }
+}
+
+// How a stone speeds up and slows down on its way to a new target
+#[derive(PartialEq, Clone, Copy)]
+pub enum EasingKind {
+    Linear,
+    EaseIn,
+    EaseOut,
+    EaseInOut,
+}
+
+impl EasingKind {
+    // Map progress from 0.0 to 1.0 onto the fraction of the distance covered
+    pub fn apply(self, t: f32) -> f32 {
+        match self {
+            EasingKind::Linear => t,
+            EasingKind::EaseIn => t * t,
+            EasingKind::EaseOut => t * (2.0 - t),
+            EasingKind::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+// How displacement and rotation grow down the grid
+#[derive(PartialEq, Clone, Copy)]
+pub enum FactorShape {
+    Linear,
+    Peak,
+}
+
+impl FactorShape {
+    // Map row position from 0.0 (top) to 1.0 (bottom) onto a factor from 0.0 to 1.0.
+    // Peak rises to 1.0 at `peak` and falls back to 0.0 at the bottom.
+    pub fn apply(self, t: f32, peak: f32) -> f32 {
+        match self {
+            FactorShape::Linear => t,
+            FactorShape::Peak if t <= peak => t / peak,
+            FactorShape::Peak => (1.0 - t) / (1.0 - peak),
+        }
+    }
+}
+
+// Everything step_stones() needs from the model
+pub struct MotionParams<'a> {
+    pub rows: u32,
+    pub motion: f32,
+    pub restlessness: f32,
+    pub cycle_min: u32,
+    pub cycle_max: u32,
+    pub disp_adj: f32,
+    pub rot_adj: f32,
+    pub rot_bias: f32,
+    pub factor_gamma: f32,
+    pub factor_shape: FactorShape,
+    pub peak: f32,
+    pub easing: EasingKind,
+    pub max_offset_x: f32,
+    pub max_offset_y: f32,
+    pub noise: Option<&'a Fbm>,
+    pub time: f64,
+}
+
+// Advance every unlocked stone one frame, picking a new target for any that have arrived
+pub fn step_stones<R: Rng>(gravel: &mut [Stone], params: &MotionParams, rng: &mut R) {
+    for stone in gravel {
+        if stone.locked {
+            continue;
+        }
+        if stone.cycles == 0 {
+            // Each stone's own restlessness, scaled by the spread, nudges its chance of moving
+            let motion = params.motion * (1.0 + params.restlessness * stone.restlessness);
+            if rng.gen::<f32>() > motion {
+                stone.x_velocity = 0.0;
+                stone.y_velocity = 0.0;
+                stone.rot_velocity = 0.0;
+                stone.cycles = rng.gen_range(params.cycle_min..params.cycle_max);
+                stone.total_cycles = stone.cycles;
+            } else {
+                let factor = params.factor_shape.apply(stone.y / params.rows as f32, params.peak).powf(params.factor_gamma);
+                let disp_factor = factor * params.disp_adj;
+                let rot_factor = factor * params.rot_adj;
+                let (new_x, new_y) = match params.noise {
+                    // Neighbouring stones get similar targets, drifting slowly over time
+                    Some(fbm) => {
+                        let (nx, ny) = (stone.x as f64 * 0.3, stone.y as f64 * 0.3);
+                        let noise_x = fbm.get([nx, ny, params.time]) as f32;
+                        let noise_y = fbm.get([nx + 100.0, ny, params.time]) as f32;
+                        (disp_factor * 0.5 * noise_x, disp_factor * 0.5 * noise_y)
+                    }
+                    None => (disp_factor * rng.gen_range(-0.5..0.5), disp_factor * rng.gen_range(-0.5..0.5)),
+                };
+                // The bias slides the range so more stones turn one way
+                let new_rot = rot_factor * (rng.gen_range(-PI / 4.0..PI / 4.0) + params.rot_bias * PI / 4.0);
+                let new_cycles = rng.gen_range(params.cycle_min..params.cycle_max);
+                stone.x_velocity = (new_x - stone.x_offset) / new_cycles as f32;
+                stone.y_velocity = (new_y - stone.y_offset) / new_cycles as f32;
+                stone.rot_velocity = (new_rot - stone.rotation) / new_cycles as f32;
+                stone.cycles = new_cycles;
+                stone.total_cycles = new_cycles;
+            }
+        } else {
+            // The velocities are the average per step; easing decides how much of it this step gets
+            let total = stone.total_cycles as f32;
+            let done = (stone.total_cycles - stone.cycles) as f32;
+            let step = total * (params.easing.apply((done + 1.0) / total) - params.easing.apply(done / total));
+            // Infinite limits leave the offsets free
+            stone.x_offset = (stone.x_offset + stone.x_velocity * step).clamp(-params.max_offset_x, params.max_offset_x);
+            stone.y_offset = (stone.y_offset + stone.y_velocity * step).clamp(-params.max_offset_y, params.max_offset_y);
+            stone.rotation += stone.rot_velocity * step;
+            stone.cycles -= 1;
+        }
+    }
+}